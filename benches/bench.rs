@@ -2,6 +2,7 @@ use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion
 use std::fs;
 use std::{path::PathBuf, time::Duration};
 
+#[allow(clippy::manual_flatten)]
 fn general_bench(c: &mut Criterion) {
     for path in fs::read_dir("roms").unwrap() {
         if let Ok(entry) = path {
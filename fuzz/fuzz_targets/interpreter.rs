@@ -0,0 +1,10 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+// See `chip8::fuzz::run` for how the bytes are used
+fuzz_target!(|data: &[u8]| {
+    if let Err(violation) = chip8::fuzz::run(data) {
+        panic!("{violation}");
+    }
+});
@@ -0,0 +1,39 @@
+use std::time::Duration;
+
+use rodio::{Device, Source};
+
+use crate::host::Audio;
+
+/// Plays the CHIP-8 tone on the default output device
+pub struct Beeper {
+    device: Device,
+    frame_length: Duration,
+    volume: f32,
+}
+
+impl Beeper {
+    /// Returns `None` when there is no audio device to play on
+    pub fn new(frame_length: Duration) -> Option<Self> {
+        rodio::default_output_device().map(|device| Self { device, frame_length, volume: 1.0 })
+    }
+    /// From 0 for silent to 100
+    pub fn set_volume(&mut self, volume: u8) {
+        self.volume = volume.min(100) as f32 / 100.0;
+    }
+    /// Plays the tone for the given duration without blocking
+    pub fn beep(&self, duration: Duration) {
+        if self.volume == 0.0 {
+            return;
+        }
+        let source = rodio::source::SineWave::new(400).amplify(self.volume);
+        rodio::play_raw(&self.device, source.take_duration(duration));
+    }
+}
+
+impl Audio for Beeper {
+    fn set_tone(&mut self, on: bool) {
+        if on {
+            self.beep(self.frame_length);
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use alloc::{
+    collections::BTreeMap,
+    format,
+    string::String,
+    vec::Vec,
+};
+use core::fmt;
+
+use crate::memview::parse_bytes;
+
+/// What a freeze holds at a value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Target {
+    Memory(u16),
+    Register(u8),
+}
+
+impl Target {
+    /// Takes a register like `V5` or a hex address like `1F0`
+    pub fn parse(word: &str) -> Result<Target, String> {
+        if let Some(register) = word.strip_prefix(['V', 'v']) {
+            return match u8::from_str_radix(register, 16) {
+                Ok(x) if x < 16 => Ok(Target::Register(x)),
+                _ => Err(format!("{word} is not a register")),
+            };
+        }
+        match u16::from_str_radix(word.trim_start_matches("0x"), 16) {
+            Ok(address) if address < 0x1000 => Ok(Target::Memory(address)),
+            _ => Err(format!("{word} is not an address or register")),
+        }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Target::Memory(address) => write!(f, "{address:03X}"),
+            Target::Register(x) => write!(f, "V{x:X}"),
+        }
+    }
+}
+
+/// Written every frame so the program cannot change it, like a lives counter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Freeze {
+    pub target: Target,
+    pub value: u8,
+}
+
+/// Written once when the ROM is loaded and again after every reset
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Patch {
+    pub address: u16,
+    pub bytes: Vec<u8>,
+}
+
+/// Freezes and patches for one ROM
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cheats {
+    freezes: Vec<Freeze>,
+    patches: Vec<Patch>,
+}
+
+impl Cheats {
+    /// Takes `freeze TARGET VALUE` or `patch ADDR BYTES...`, anything after `#` is a comment
+    pub fn parse_line(&mut self, line: &str) -> Result<(), String> {
+        let line = line.split('#').next().unwrap_or_default();
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words.as_slice() {
+            [] => {}
+            ["freeze", target, value] => {
+                let value = u8::from_str_radix(value.trim_start_matches("0x"), 16)
+                    .map_err(|_| format!("{value} is not a hex byte"))?;
+                self.freeze(Target::parse(target)?, value);
+            }
+            ["patch", address, bytes @ ..] if !bytes.is_empty() => {
+                let Ok(Target::Memory(address)) = Target::parse(address) else {
+                    return Err(format!("{address} is not an address"));
+                };
+                self.patch(address, parse_bytes(bytes.iter().copied())?);
+            }
+            _ => return Err(String::from("expected freeze TARGET VALUE or patch ADDR BYTES...")),
+        }
+        Ok(())
+    }
+    /// Replaces any freeze of the same target
+    pub fn freeze(&mut self, target: Target, value: u8) {
+        self.unfreeze(target);
+        self.freezes.push(Freeze { target, value });
+    }
+    /// Returns whether the target was frozen
+    pub fn unfreeze(&mut self, target: Target) -> bool {
+        let len = self.freezes.len();
+        self.freezes.retain(|freeze| freeze.target != target);
+        self.freezes.len() != len
+    }
+
+    pub fn patch(&mut self, address: u16, bytes: Vec<u8>) {
+        self.patches.push(Patch { address, bytes });
+    }
+
+    pub fn freezes(&self) -> &[Freeze] {
+        &self.freezes
+    }
+
+    pub fn patches(&self) -> &[Patch] {
+        &self.patches
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.freezes.is_empty() && self.patches.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.freezes.clear();
+        self.patches.clear();
+    }
+    /// Freezes then patches, one to a line in the format `parse_line` takes
+    pub fn lines(&self) -> Vec<String> {
+        let freezes = self.freezes.iter().map(|freeze| format!("freeze {} {:02X}", freeze.target, freeze.value));
+        let patches = self.patches.iter().map(|patch| {
+            let bytes: Vec<String> = patch.bytes.iter().map(|byte| format!("{byte:02X}")).collect();
+            format!("patch {:03X} {}", patch.address, bytes.join(" "))
+        });
+        freezes.chain(patches).collect()
+    }
+}
+
+/// Cheats for many ROMs. Lines before the first `[sha1]` section apply to every ROM, the lines of a
+/// section only to the ROM with that hash
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CheatFile {
+    global: Cheats,
+    roms: BTreeMap<String, Cheats>,
+}
+
+impl CheatFile {
+    pub fn parse(text: &str) -> Result<CheatFile, String> {
+        let mut file = CheatFile::default();
+        let mut section = None;
+        for (n, line) in text.lines().enumerate() {
+            let trimmed = line.trim();
+            if let Some(hash) = trimmed.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                section = Some(hash.trim().to_ascii_lowercase());
+                continue;
+            }
+            let cheats = match &section {
+                Some(hash) => file.roms.entry(hash.clone()).or_default(),
+                None => &mut file.global,
+            };
+            cheats.parse_line(trimmed).map_err(|e| format!("line {}: {e}", n + 1))?;
+        }
+        Ok(file)
+    }
+    /// Cheats that apply to the ROM with this SHA-1
+    pub fn cheats(&self, rom_hash: Option<&str>) -> Cheats {
+        let mut cheats = self.global.clone();
+        if let Some(rom) = rom_hash.and_then(|hash| self.roms.get(&hash.to_ascii_lowercase())) {
+            for freeze in &rom.freezes {
+                cheats.freeze(freeze.target, freeze.value);
+            }
+            cheats.patches.extend(rom.patches.iter().cloned());
+        }
+        cheats
+    }
+}
+
+/// How a RAM search compares every candidate byte with its value at the last search
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Comparison {
+    Unchanged,
+    Changed,
+    Increased,
+    Decreased,
+    /// Is this value now, whatever it was before
+    Equal(u8),
+}
+
+impl Comparison {
+    /// Takes `eq`, `changed`, `inc` or `dec`, with a value `eq` compares to it instead of the last search
+    pub fn parse(name: &str, value: Option<u8>) -> Option<Comparison> {
+        match (name, value) {
+            ("eq", None) => Some(Comparison::Unchanged),
+            ("eq", Some(value)) => Some(Comparison::Equal(value)),
+            ("changed" | "ne", None) => Some(Comparison::Changed),
+            ("inc", None) => Some(Comparison::Increased),
+            ("dec", None) => Some(Comparison::Decreased),
+            _ => None,
+        }
+    }
+
+    fn matches(&self, before: u8, now: u8) -> bool {
+        match self {
+            Comparison::Unchanged => now == before,
+            Comparison::Changed => now != before,
+            Comparison::Increased => now > before,
+            Comparison::Decreased => now < before,
+            Comparison::Equal(value) => now == *value,
+        }
+    }
+}
+
+/// Finds where a program keeps a value by narrowing down addresses across snapshots of memory,
+/// like losing a life and keeping only the bytes that decreased
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RamSearch {
+    snapshot: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl RamSearch {
+    /// Every address is a candidate to start with
+    pub fn new(memory: &[u8]) -> Self {
+        Self {
+            snapshot: memory.to_vec(),
+            candidates: (0..memory.len() as u16).collect(),
+        }
+    }
+    /// Keeps the candidates that match and takes a new snapshot to compare the next search with
+    pub fn narrow(&mut self, memory: &[u8], comparison: Comparison) {
+        self.candidates.retain(|address| {
+            let address = *address as usize;
+            comparison.matches(self.snapshot[address], memory[address])
+        });
+        self.snapshot = memory.to_vec();
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+    /// Value at the last snapshot
+    pub fn previous(&self, address: u16) -> u8 {
+        self.snapshot[address as usize]
+    }
+}
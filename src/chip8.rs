@@ -1,6 +1,18 @@
+use crate::cheat::{CheatFile, Cheats, Target};
+use crate::coverage::Coverage;
 use crate::font::FONT_SET;
+use crate::memview::{hex_view, ROW_BYTES};
+use crate::observer::Observer;
+use crate::profile::Profiler;
+use crate::quirks::Quirks;
+use crate::timing::{vip_cycles, Timing, VIP_FRAME_BUDGET};
+#[cfg(feature = "std")]
+use crate::trace::{TraceRecord, Tracer};
 use fastrand::Rng;
 use opcode_macros::opcode_handler;
+use alloc::{boxed::Box, collections::VecDeque, string::{String, ToString}, vec::Vec};
+use core::fmt;
+#[cfg(feature = "std")]
 use std::{
     fs::File,
     io::{Error, Read},
@@ -10,8 +22,181 @@ use std::{
 pub const WIDTH: usize = 64;
 pub const HEIGHT: usize = 32;
 
-const PIXEL_ON: &[u8] = &[255, 255, 255, 255];
-const PIXEL_OFF: &[u8] = &[0, 0, 0, 255];
+/// Frames a pixel takes to fade out when cycling into `DisplayMode::Persistence`
+const DEFAULT_PERSISTENCE: u8 = 4;
+/// Bounds of instructions executed per frame, see `set_speed`
+pub const MIN_SPEED: u32 = 1;
+pub const MAX_SPEED: u32 = 250;
+/// Roughly 660 instructions per second, which most games are written for
+pub const DEFAULT_SPEED: u32 = 11;
+/// Frames presented and timer ticks per second
+pub const FRAME_RATE: u32 = 60;
+/// Addresses wrap around the 4 KiB of memory
+pub(crate) const ADDRESS_MASK: u16 = 0x0FFF;
+/// Fast forward runs this many frames worth of instructions every frame
+const FAST_FORWARD_FACTOR: u32 = 8;
+/// Slow motion only runs one out of this many frames
+const SLOW_MOTION_FACTOR: u32 = 4;
+
+/// How `vram` is turned into pixels every time the frame is presented
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DisplayMode {
+    /// Copy `vram` as is, sprites erased and redrawn every frame will flicker
+    Raw,
+    /// Pixels that turn off fade out over the given number of frames
+    Persistence(u8),
+    /// Show every pixel that was lit at any point since the last present
+    Vblank,
+}
+
+impl DisplayMode {
+    /// Takes `raw`, `vblank`, `persistence` or `persistence:<frames>`
+    pub fn parse(value: &str) -> Option<DisplayMode> {
+        match value.split_once(':') {
+            Some(("persistence", frames)) => frames.parse().ok().map(DisplayMode::Persistence),
+            Some(_) => None,
+            None => match value {
+                "raw" => Some(DisplayMode::Raw),
+                "persistence" => Some(DisplayMode::Persistence(DEFAULT_PERSISTENCE)),
+                "vblank" => Some(DisplayMode::Vblank),
+                _ => None,
+            },
+        }
+    }
+}
+
+impl fmt::Display for DisplayMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DisplayMode::Raw => write!(f, "raw"),
+            DisplayMode::Persistence(frames) => write!(f, "persistence:{frames}"),
+            DisplayMode::Vblank => write!(f, "vblank"),
+        }
+    }
+}
+
+/// Why the interpreter stopped running a ROM
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Fault {
+    /// `2nnn` with all 16 levels of the stack in use
+    StackOverflow { pc: u16 },
+    /// `00EE` with nothing on the stack
+    StackUnderflow { pc: u16 },
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Fault::StackOverflow { pc } => write!(f, "stack overflow at {pc:03X}"),
+            Fault::StackUnderflow { pc } => write!(f, "stack underflow at {pc:03X}"),
+        }
+    }
+}
+
+/// Where `Fx0A` is in waiting for a key
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum KeyWait {
+    Idle,
+    /// Waiting for any key to go down
+    Pressing,
+    /// Waiting for this key to be let go
+    Held(u8),
+}
+
+/// A keypad key going down or up once `cycle` instructions have run since the ROM started
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub cycle: u64,
+    /// From 0 to F
+    pub key: u8,
+    pub pressed: bool,
+}
+
+impl KeyEvent {
+    /// Reads a line as written by `Display`, like `1234 A down`
+    pub fn parse(line: &str) -> Option<KeyEvent> {
+        let mut words = line.split_whitespace();
+        let cycle = words.next()?.parse().ok()?;
+        let key = u8::from_str_radix(words.next()?, 16).ok().filter(|key| *key < 16)?;
+        let pressed = match words.next()? {
+            "down" => true,
+            "up" => false,
+            _ => return None,
+        };
+        words.next().is_none().then_some(KeyEvent { cycle, key, pressed })
+    }
+}
+
+impl fmt::Display for KeyEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {:X} {}", self.cycle, self.key, if self.pressed { "down" } else { "up" })
+    }
+}
+
+/// Colours of lit and unlit pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    pub on: [u8; 3],
+    pub off: [u8; 3],
+}
+
+impl Palette {
+    pub const MONO: Palette = Palette { on: [255, 255, 255], off: [0, 0, 0] };
+    pub const AMBER: Palette = Palette { on: [255, 176, 0], off: [24, 14, 0] };
+    pub const GREEN: Palette = Palette { on: [51, 255, 102], off: [0, 22, 6] };
+    pub const LCD: Palette = Palette { on: [15, 56, 15], off: [155, 188, 15] };
+    /// Names accepted by `parse`
+    pub const PRESETS: [&'static str; 4] = ["mono", "amber", "green", "lcd"];
+
+    /// Takes a preset name or two colours as `RRGGBB,RRGGBB` for lit and unlit pixels
+    pub fn parse(value: &str) -> Option<Palette> {
+        match value {
+            "mono" => Some(Palette::MONO),
+            "amber" => Some(Palette::AMBER),
+            "green" => Some(Palette::GREEN),
+            "lcd" => Some(Palette::LCD),
+            _ => {
+                let (on, off) = value.split_once(',')?;
+                Some(Palette { on: parse_colour(on)?, off: parse_colour(off)? })
+            }
+        }
+    }
+    /// Colour between unlit at level 0 and lit at level 255
+    fn blend(&self, level: u8) -> [u8; 4] {
+        let channel = |i: usize| {
+            let (on, off) = (self.on[i] as i32, self.off[i] as i32);
+            (off + (on - off) * level as i32 / 255) as u8
+        };
+        [channel(0), channel(1), channel(2), 255]
+    }
+}
+
+/// Preset name, or the colours as `RRGGBB,RRGGBB`
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = Palette::PRESETS.into_iter().find(|name| Palette::parse(name) == Some(*self)) {
+            return write!(f, "{name}");
+        }
+        let ([r, g, b], [r2, g2, b2]) = (self.on, self.off);
+        write!(f, "{r:02x}{g:02x}{b:02x},{r2:02x}{g2:02x}{b2:02x}")
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Palette::MONO
+    }
+}
+
+/// Parses colours written as `RRGGBB`, with an optional leading `#`
+pub fn parse_colour(value: &str) -> Option<[u8; 3]> {
+    let value = value.trim().trim_start_matches('#');
+    if value.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(value.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
 
 pub struct Chip8Interpreter {
     memory: [u8; 4096],
@@ -21,18 +206,184 @@ pub struct Chip8Interpreter {
     stack: [u16; 16],
     stack_ptr: usize,
     rng: Rng,
+    seed: Option<u64>,
     vram: [bool; WIDTH * HEIGHT],
     delay_timer: u8,
     sound_timer: u8,
     should_execute: bool,
+    // Set when the ROM did something that stopped it, until the next reset
+    fault: Option<Fault>,
     keyboard: [bool; 16],
-    total_dt: u16,
-    total_dt2: u16,
+    // Keys that went down since `Fx0A` started waiting, a bit for each
+    key_presses: u16,
+    key_wait: KeyWait,
+    // Key events waiting for the instruction boundary they happen at, oldest first
+    key_events: VecDeque<KeyEvent>,
+    // Keys that went down but no instruction has looked at yet, releasing them waits
+    unread_keys: u16,
+    // Keys that went down this frame, they stop holding back their release at the end of the next
+    frame_presses: u16,
+    // Every key event as it was applied, when recording
+    key_record: Option<Vec<KeyEvent>>,
     debug: u8,
-    debug_iter: u32,
+    // Instructions run since the ROM was loaded, numbers debug output and traces
+    debug_iter: u64,
+    display_mode: DisplayMode,
+    persistence_frames: u8,
+    // Frames left before a pixel has fully faded, used by `DisplayMode::Persistence`
+    phosphor: [u8; WIDTH * HEIGHT],
+    // OR of every `vram` state since the last present, used by `DisplayMode::Vblank`
+    vram_since_present: [bool; WIDTH * HEIGHT],
+    // Kept so the machine can be reset without loading the file again
+    rom: Option<Vec<u8>>,
+    // SHA-1 of `rom` in lowercase hex
+    rom_hash: Option<String>,
+    speed: u32,
+    paused: bool,
+    fast_forward: bool,
+    slow_motion: bool,
+    frame_count: u32,
+    // Every instruction executed since the interpreter was created, kept across resets
+    instructions: u64,
+    // Set by `Dxyn` with the display wait quirk, ends the frame early
+    waiting_for_vblank: bool,
+    // Instructions, or machine cycles with VIP timing, run so far in the current frame.
+    // Frames can be stepped through one instruction at a time
+    frame_cycles: u32,
+    palette: Palette,
+    quirks: Quirks,
+    timing: Timing,
+    #[cfg(feature = "std")]
+    tracer: Option<Tracer>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    observer: Option<Box<dyn Observer>>,
+    // `should_beep` as the observer last heard it
+    beeping: bool,
+    // Cheats for the loaded ROM, picked from `cheat_file` when one is set
+    cheats: Cheats,
+    cheat_file: Option<CheatFile>,
+}
+
+impl Default for Chip8Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Clones the whole machine and its settings, but not the tracer or observer so two machines never share one
+impl Clone for Chip8Interpreter {
+    fn clone(&self) -> Self {
+        Self {
+            memory: self.memory,
+            registers: self.registers,
+            address: self.address,
+            program_counter: self.program_counter,
+            stack: self.stack,
+            stack_ptr: self.stack_ptr,
+            rng: self.rng.clone(),
+            seed: self.seed,
+            vram: self.vram,
+            delay_timer: self.delay_timer,
+            sound_timer: self.sound_timer,
+            should_execute: self.should_execute,
+            fault: self.fault,
+            keyboard: self.keyboard,
+            key_presses: self.key_presses,
+            key_wait: self.key_wait,
+            key_events: self.key_events.clone(),
+            unread_keys: self.unread_keys,
+            frame_presses: self.frame_presses,
+            key_record: self.key_record.clone(),
+            debug: self.debug,
+            debug_iter: self.debug_iter,
+            display_mode: self.display_mode,
+            persistence_frames: self.persistence_frames,
+            phosphor: self.phosphor,
+            vram_since_present: self.vram_since_present,
+            rom: self.rom.clone(),
+            rom_hash: self.rom_hash.clone(),
+            speed: self.speed,
+            paused: self.paused,
+            fast_forward: self.fast_forward,
+            slow_motion: self.slow_motion,
+            frame_count: self.frame_count,
+            instructions: self.instructions,
+            waiting_for_vblank: self.waiting_for_vblank,
+            frame_cycles: self.frame_cycles,
+            palette: self.palette,
+            quirks: self.quirks,
+            timing: self.timing,
+            #[cfg(feature = "std")]
+            tracer: None,
+            profiler: self.profiler.clone(),
+            coverage: self.coverage.clone(),
+            observer: None,
+            beeping: false,
+            cheats: self.cheats.clone(),
+            cheat_file: self.cheat_file.clone(),
+        }
+    }
+}
+
+/// Machines are equal when they will run the same from here on given the same keys. The total instruction
+/// count, the display mode and attached tools are not compared
+impl PartialEq for Chip8Interpreter {
+    fn eq(&self, other: &Self) -> bool {
+        self.memory == other.memory
+            && self.registers == other.registers
+            && self.address == other.address
+            && self.program_counter == other.program_counter
+            && self.stack() == other.stack()
+            && self.rng == other.rng
+            && self.vram == other.vram
+            && self.delay_timer == other.delay_timer
+            && self.sound_timer == other.sound_timer
+            && self.should_execute == other.should_execute
+            && self.fault == other.fault
+            && self.keyboard == other.keyboard
+            && self.key_presses == other.key_presses
+            && self.key_wait == other.key_wait
+            && self.key_events == other.key_events
+            && self.unread_keys == other.unread_keys
+            && self.frame_presses == other.frame_presses
+            && self.waiting_for_vblank == other.waiting_for_vblank
+            && self.frame_cycles == other.frame_cycles
+            && self.debug_iter == other.debug_iter
+            && self.speed == other.speed
+            && self.quirks == other.quirks
+            && self.timing == other.timing
+            && self.cheats == other.cheats
+    }
+}
+
+/// Registers and state that fit on a few lines, memory and the display are left out
+impl fmt::Debug for Chip8Interpreter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Chip8Interpreter")
+            .field("registers", &self.registers)
+            .field("index", &self.address)
+            .field("program_counter", &self.program_counter)
+            .field("stack", &self.stack())
+            .field("delay_timer", &self.delay_timer)
+            .field("sound_timer", &self.sound_timer)
+            .field("keyboard", &self.keyboard)
+            .field("running", &self.should_execute)
+            .field("fault", &self.fault)
+            .field("instructions", &self.instructions)
+            .field("rom_hash", &self.rom_hash)
+            .field("quirks", &self.quirks)
+            .field("timing", &self.timing)
+            .finish_non_exhaustive()
+    }
 }
 
 impl Chip8Interpreter {
+    /// Starts a machine in a chosen state, see `Chip8InterpreterBuilder`
+    pub fn builder() -> Chip8InterpreterBuilder {
+        Chip8InterpreterBuilder::new()
+    }
+
     pub fn new() -> Self {
         let mut mem = [0; 4096];
         mem[..FONT_SET.len()].copy_from_slice(&FONT_SET);
@@ -46,44 +397,540 @@ impl Chip8Interpreter {
             delay_timer: 0,
             sound_timer: 0,
             vram: [false; WIDTH * HEIGHT],
+            #[cfg(feature = "std")]
             rng: Rng::new(),
+            // Without std there is no entropy source, see `set_seed`
+            #[cfg(not(feature = "std"))]
+            rng: Rng::with_seed(0),
+            seed: None,
             should_execute: false,
+            fault: None,
             keyboard: [false; 16],
-            total_dt: 0,
-            total_dt2: 0,
+            key_presses: 0,
+            key_wait: KeyWait::Idle,
+            key_events: VecDeque::new(),
+            unread_keys: 0,
+            frame_presses: 0,
+            key_record: None,
             debug: 0,
             debug_iter: 0,
+            display_mode: DisplayMode::Raw,
+            persistence_frames: DEFAULT_PERSISTENCE,
+            phosphor: [0; WIDTH * HEIGHT],
+            vram_since_present: [false; WIDTH * HEIGHT],
+            rom: None,
+            rom_hash: None,
+            speed: DEFAULT_SPEED,
+            paused: false,
+            fast_forward: false,
+            slow_motion: false,
+            frame_count: 0,
+            instructions: 0,
+            waiting_for_vblank: false,
+            frame_cycles: 0,
+            palette: Palette::MONO,
+            quirks: Quirks::MODERN,
+            timing: Timing::Instructions,
+            #[cfg(feature = "std")]
+            tracer: None,
+            profiler: None,
+            coverage: None,
+            observer: None,
+            beeping: false,
+            cheats: Cheats::default(),
+            cheat_file: None,
         }
     }
-    
+
+    /// Changes a key right away, use `queue_key` so quick taps between frames are not missed.
+    /// Keys past F are ignored
     pub fn update_key(&mut self, position: usize, state: bool) {
+        if position > 0xF {
+            return;
+        }
+        if state && !self.keyboard[position] {
+            self.key_presses |= 1 << position;
+        }
         self.keyboard[position] = state;
     }
 
+    /// Changes a key before the next instruction. A release waits until an instruction has looked
+    /// at the key, or the frame after it was pressed has ended, so every press can be seen. Keys past F are ignored
+    pub fn queue_key(&mut self, position: usize, state: bool) {
+        if position > 0xF {
+            return;
+        }
+        self.queue_key_event(KeyEvent { cycle: self.debug_iter, key: position as u8, pressed: state });
+    }
+    /// Changes a key once `event.cycle` instructions have run, used to replay recorded events.
+    /// Keys past F are ignored
+    pub fn queue_key_event(&mut self, event: KeyEvent) {
+        if event.key > 0xF {
+            return;
+        }
+        // Kept in order so no event is applied before one that came earlier
+        let at = self.key_events.partition_point(|queued| queued.cycle <= event.cycle);
+        self.key_events.insert(at, event);
+    }
+    /// Starts or stops keeping every key event as it is applied. Kept across resets
+    pub fn record_keys(&mut self, record: bool) {
+        self.key_record = record.then(Vec::new);
+    }
+    /// Key events applied since recording started or the last reset, replaying them with
+    /// `queue_key_event` plays out the same way
+    pub fn recorded_keys(&self) -> &[KeyEvent] {
+        self.key_record.as_deref().unwrap_or_default()
+    }
+    /// Applies queued key events that are due, stopping at a release of a key not yet read
+    fn apply_key_events(&mut self) {
+        while let Some(event) = self.key_events.front().copied() {
+            let bit = 1 << event.key;
+            if event.cycle > self.debug_iter || (!event.pressed && self.unread_keys & bit != 0) {
+                break;
+            }
+            self.key_events.pop_front();
+            if event.pressed && !self.keyboard[event.key as usize] {
+                self.unread_keys |= bit;
+                self.frame_presses |= bit;
+            }
+            self.update_key(event.key as usize, event.pressed);
+            if let Some(record) = &mut self.key_record {
+                record.push(KeyEvent { cycle: self.debug_iter, ..event });
+            }
+        }
+    }
+    /// Whether a key is down, marking it as read by the program
+    fn read_key(&mut self, key: usize) -> bool {
+        self.unread_keys &= !(1 << key);
+        self.keyboard[key]
+    }
+
+    /// Audio is muted while fast forwarding. Like the VIP, a tone also sounds while the key
+    /// `Fx0A` is waiting on is held down
     pub fn should_beep(&self) -> bool {
-        self.sound_timer > 0
+        (self.sound_timer > 0 || matches!(self.key_wait, KeyWait::Held(_))) && !self.fast_forward
     }
     /// Sets the level of logging based on args
     pub fn set_debug(&mut self, value: u8) {
         self.debug = value;
         // Deterministic when debugging
-        self.rng = Rng::with_seed(0);
-        println!("debug set: {}", value);
+        self.set_seed(0);
+        log!("debug set: {}", value);
+    }
+    /// Starts writing a record for every executed instruction, or stops with `None`. Kept across resets
+    #[cfg(feature = "std")]
+    pub fn set_tracer(&mut self, tracer: Option<Tracer>) {
+        self.tracer = tracer;
+    }
+    /// Starts counting where instructions run, or stops with `None`. Kept across resets
+    pub fn set_profiler(&mut self, profiler: Option<Profiler>) {
+        self.profiler = profiler;
+    }
+
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.profiler.as_ref()
+    }
+    /// Starts recording how each byte of memory is used, or stops with `None`. Kept across resets
+    pub fn set_coverage(&mut self, coverage: Option<Coverage>) {
+        self.coverage = coverage;
+    }
+
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_ref()
+    }
+    /// Calls an observer as the machine runs. Without one every event costs a single check
+    pub fn set_observer(&mut self, observer: Option<Box<dyn Observer>>) {
+        self.observer = observer;
+        self.beeping = self.should_beep();
+    }
+    /// Removes the observer, to get back one that was installed by value
+    pub fn take_observer(&mut self) -> Option<Box<dyn Observer>> {
+        self.observer.take()
+    }
+    /// Makes `Cxkk` deterministic, kept across resets
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+        self.rng = Rng::with_seed(seed);
+    }
+    /// Changes how frames are presented, can be done while a ROM is running
+    pub fn set_display_mode(&mut self, mode: DisplayMode) {
+        if let DisplayMode::Persistence(frames @ 1..) = mode {
+            self.persistence_frames = frames;
+        }
+        self.display_mode = mode;
+        self.phosphor = [0; WIDTH * HEIGHT];
+        self.vram_since_present = self.vram;
+    }
+
+    pub fn display_mode(&self) -> DisplayMode {
+        self.display_mode
+    }
+
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    pub fn palette(&self) -> Palette {
+        self.palette
+    }
+    /// Changes the behaviour of ambiguous instructions, kept across resets
+    pub fn set_quirks(&mut self, quirks: Quirks) {
+        self.quirks = quirks;
+    }
+
+    pub fn quirks(&self) -> Quirks {
+        self.quirks
+    }
+    /// With `Timing::Vip` the speed is ignored and frames run as many instructions as fit in the VIP's cycles
+    pub fn set_timing(&mut self, timing: Timing) {
+        self.timing = timing;
+        // Cycles counted one way mean nothing to the other, so the frame starts over
+        self.frame_cycles = 0;
+    }
+
+    pub fn timing(&self) -> Timing {
+        self.timing
+    }
+    /// Current state of every pixel, row by row
+    pub fn vram(&self) -> &[bool] {
+        &self.vram
+    }
+    /// All 4096 bytes, including the font and the loaded ROM
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+    /// Memory for a debugger to edit, changes are undone by a reset
+    pub fn memory_mut(&mut self) -> &mut [u8] {
+        &mut self.memory
+    }
+    /// V0 to VF
+    pub fn registers(&self) -> &[u8] {
+        &self.registers
+    }
+    /// The I register
+    pub fn index(&self) -> u16 {
+        self.address
+    }
+    /// Address of the next instruction to execute
+    pub fn program_counter(&self) -> u16 {
+        self.program_counter
+    }
+    /// Return addresses of the subroutines being run, the innermost last
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.stack_ptr]
+    }
+
+    /// Byte at an address, wrapping around the end of memory
+    pub fn read_u8(&self, address: u16) -> u8 {
+        self.memory[(address & ADDRESS_MASK) as usize]
+    }
+    /// Big endian word at an address like an instruction is fetched, wrapping around the end of memory
+    pub fn read_u16(&self, address: u16) -> u16 {
+        u16::from_be_bytes([self.read_u8(address), self.read_u8(address.wrapping_add(1))])
+    }
+    /// Writes a byte, wrapping around the end of memory. Unlike the ROM's own stores the font is not protected
+    pub fn write_u8(&mut self, address: u16, value: u8) {
+        self.memory[(address & ADDRESS_MASK) as usize] = value;
+    }
+    /// Writes a big endian word, wrapping around the end of memory
+    pub fn write_u16(&mut self, address: u16, value: u16) {
+        let [high, low] = value.to_be_bytes();
+        self.write_u8(address, high);
+        self.write_u8(address.wrapping_add(1), low);
+    }
+    /// Writes bytes from an address on, wrapping around the end of memory
+    pub fn write_bytes(&mut self, address: u16, bytes: &[u8]) {
+        for (offset, byte) in bytes.iter().enumerate() {
+            self.write_u8(address.wrapping_add(offset as u16), *byte);
+        }
+    }
+    /// Sets Vx, `x` is taken modulo 16
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.registers[x & 0xF] = value;
+    }
+    /// Sets I, only the low 12 bits are kept
+    pub fn set_index(&mut self, index: u16) {
+        self.address = index & ADDRESS_MASK;
+    }
+    /// Jumps to an address, only the low 12 bits are kept
+    pub fn set_program_counter(&mut self, address: u16) {
+        self.program_counter = address & ADDRESS_MASK;
+    }
+    /// Replaces the return addresses, the innermost last. Only the low 12 bits of each are kept,
+    /// and only the first 16 addresses since a call past them would overflow the stack
+    pub fn set_stack(&mut self, stack: &[u16]) {
+        let stack = &stack[..stack.len().min(self.stack.len())];
+        for (slot, address) in self.stack.iter_mut().zip(stack) {
+            *slot = address & ADDRESS_MASK;
+        }
+        self.stack_ptr = stack.len();
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+    /// Which of the 16 keys are down
+    pub fn keyboard(&self) -> &[bool] {
+        &self.keyboard
+    }
+    /// Switches to the next display mode, using the last persistence length that was set
+    pub fn cycle_display_mode(&mut self) {
+        let mode = match self.display_mode {
+            DisplayMode::Raw => DisplayMode::Persistence(self.persistence_frames),
+            DisplayMode::Persistence(_) => DisplayMode::Vblank,
+            DisplayMode::Vblank => DisplayMode::Raw,
+        };
+        self.set_display_mode(mode);
     }
     /// Given a path to a file, load it into memory and execute it
+    #[cfg(feature = "std")]
     pub fn load_rom(&mut self, f: PathBuf) -> Result<(), Error> {
-        let debug = self.debug;
-        *self = Self::new();
-        // Preserve debug value
-        if debug > 0 {
-            self.debug = debug;
-            println!("Loading: {f:?}");
-        }
-        let mut file = File::open(f)?;
-        file.read(&mut self.memory[0x200..])?;
-        self.should_execute = true;
+        if self.debug > 0 {
+            log!("Loading: {f:?}");
+        }
+        let mut rom = Vec::new();
+        File::open(f)?.read_to_end(&mut rom)?;
+        self.load_bytes(&rom);
         Ok(())
     }
+    /// Loads a ROM already in memory and executes it, anything past the end of memory is ignored
+    pub fn load_bytes(&mut self, rom: &[u8]) {
+        self.rom = Some(rom.to_vec());
+        self.rom_hash = Some(sha1_smol::Sha1::from(rom).digest().to_string());
+        self.cheats = self.cheat_file.as_ref().map(|file| file.cheats(self.rom_hash.as_deref())).unwrap_or_default();
+        self.reset();
+    }
+    /// The loaded ROM as it was before running
+    pub fn rom(&self) -> Option<&[u8]> {
+        self.rom.as_deref()
+    }
+    /// SHA-1 of the loaded ROM in lowercase hex, identifies it regardless of its file name
+    pub fn rom_hash(&self) -> Option<&str> {
+        self.rom_hash.as_deref()
+    }
+    /// Restarts the currently loaded ROM from a clean machine, keeping frontend settings
+    pub fn reset(&mut self) {
+        let fresh = Self::new();
+        self.memory = fresh.memory;
+        self.registers = fresh.registers;
+        self.address = fresh.address;
+        self.program_counter = fresh.program_counter;
+        self.stack = fresh.stack;
+        self.stack_ptr = fresh.stack_ptr;
+        self.vram = fresh.vram;
+        self.delay_timer = fresh.delay_timer;
+        self.sound_timer = fresh.sound_timer;
+        self.keyboard = fresh.keyboard;
+        self.key_presses = fresh.key_presses;
+        self.key_wait = fresh.key_wait;
+        self.key_events = fresh.key_events;
+        self.unread_keys = fresh.unread_keys;
+        self.frame_presses = fresh.frame_presses;
+        if let Some(record) = &mut self.key_record {
+            record.clear();
+        }
+        self.debug_iter = fresh.debug_iter;
+        self.phosphor = fresh.phosphor;
+        self.vram_since_present = fresh.vram_since_present;
+        self.paused = fresh.paused;
+        self.fast_forward = fresh.fast_forward;
+        self.frame_count = fresh.frame_count;
+        self.waiting_for_vblank = fresh.waiting_for_vblank;
+        self.frame_cycles = fresh.frame_cycles;
+        self.rng = match self.seed {
+            Some(seed) => Rng::with_seed(seed),
+            None => fresh.rng,
+        };
+        self.should_execute = false;
+        self.fault = fresh.fault;
+        if let Some(rom) = &self.rom {
+            let len = rom.len().min(self.memory.len() - 0x200);
+            self.memory[0x200..0x200 + len].copy_from_slice(&rom[..len]);
+            self.should_execute = true;
+        }
+        self.apply_patches();
+        self.apply_freezes();
+    }
+    /// Every ROM loaded from now on gets its cheats from the file, and the loaded ROM gets them right away
+    pub fn set_cheat_file(&mut self, file: Option<CheatFile>) {
+        self.cheat_file = file;
+        let cheats = self.cheat_file.as_ref().map(|file| file.cheats(self.rom_hash.as_deref())).unwrap_or_default();
+        self.set_cheats(cheats);
+    }
+    /// Replaces the cheats of the loaded ROM until another is loaded, patches are written right away
+    pub fn set_cheats(&mut self, cheats: Cheats) {
+        self.cheats = cheats;
+        self.apply_patches();
+        self.apply_freezes();
+    }
+
+    pub fn cheats(&self) -> &Cheats {
+        &self.cheats
+    }
+    /// Holds a byte of memory or a register at a value, written now and at the end of every frame
+    pub fn freeze(&mut self, target: Target, value: u8) {
+        self.cheats.freeze(target, value);
+        self.apply_freezes();
+    }
+    /// Returns whether the target was frozen
+    pub fn unfreeze(&mut self, target: Target) -> bool {
+        self.cheats.unfreeze(target)
+    }
+    /// Writes bytes now and again after every reset
+    pub fn patch(&mut self, address: u16, bytes: &[u8]) {
+        self.write_bytes(address, bytes);
+        self.cheats.patch(address, bytes.to_vec());
+    }
+
+    fn apply_patches(&mut self) {
+        for patch in self.cheats.patches() {
+            for (offset, byte) in patch.bytes.iter().enumerate() {
+                self.memory[(patch.address as usize + offset) & ADDRESS_MASK as usize] = *byte;
+            }
+        }
+    }
+
+    fn apply_freezes(&mut self) {
+        for freeze in self.cheats.freezes() {
+            match freeze.target {
+                Target::Memory(address) => self.memory[(address & ADDRESS_MASK) as usize] = freeze.value,
+                Target::Register(x) => self.registers[x as usize & 0xF] = freeze.value,
+            }
+        }
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    pub fn toggle_pause(&mut self) {
+        self.paused = !self.paused;
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+    /// What stopped the ROM, if anything did
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+    /// Sets instructions executed per frame, clamped between `MIN_SPEED` and `MAX_SPEED`.
+    /// Multiply by `FRAME_RATE` for instructions per second
+    pub fn set_speed(&mut self, speed: u32) {
+        self.speed = speed.clamp(MIN_SPEED, MAX_SPEED);
+    }
+
+    pub fn speed(&self) -> u32 {
+        self.speed
+    }
+    /// Runs several frames worth of instructions per frame, muting audio
+    pub fn set_fast_forward(&mut self, fast_forward: bool) {
+        self.fast_forward = fast_forward;
+        self.notify_sound();
+    }
+
+    pub fn is_fast_forward(&self) -> bool {
+        self.fast_forward
+    }
+    /// Only runs a fraction of frames
+    pub fn set_slow_motion(&mut self, slow_motion: bool) {
+        self.slow_motion = slow_motion;
+    }
+
+    pub fn is_slow_motion(&self) -> bool {
+        self.slow_motion
+    }
+    /// Runs one frame worth of instructions, taking pause, fast forward and slow motion into account
+    pub fn run_frame(&mut self) {
+        if self.paused {
+            return;
+        }
+        self.frame_count = self.frame_count.wrapping_add(1);
+        if self.slow_motion && !self.frame_count.is_multiple_of(SLOW_MOTION_FACTOR) {
+            return;
+        }
+        let frames = match self.fast_forward {
+            true => FAST_FORWARD_FACTOR,
+            false => 1,
+        };
+        for _ in 0..frames {
+            self.step_frame();
+        }
+    }
+    /// Instructions executed since the interpreter was created, used to measure speed
+    pub fn instruction_count(&self) -> u64 {
+        self.instructions
+    }
+    /// Runs the rest of the current frame's instructions and ticks the timers, even while paused
+    pub fn step_frame(&mut self) {
+        while !self.step_instruction() {}
+    }
+    /// Runs a single instruction, ticking the timers once a frame's worth have run.
+    /// Returns whether that ended the frame
+    pub fn step_instruction(&mut self) -> bool {
+        let (cost, budget) = match self.timing {
+            Timing::Instructions => (1, self.speed),
+            Timing::Vip => {
+                // Fetched the way `execute_cycle` will, wrapping at the end of memory
+                let pc = self.program_counter as usize;
+                let opcode = u16::from_be_bytes([self.memory[pc], self.memory[(pc + 1) & ADDRESS_MASK as usize]]);
+                (vip_cycles(opcode, &self.registers, self.address), VIP_FRAME_BUDGET)
+            }
+        };
+        self.execute_cycle();
+        self.frame_cycles += cost;
+        if self.frame_cycles < budget && !self.waiting_for_vblank {
+            return false;
+        }
+        // An instruction still running at the end of a frame finishes in the next one
+        self.frame_cycles = match self.waiting_for_vblank {
+            true => 0,
+            false => self.frame_cycles - budget,
+        };
+        self.waiting_for_vblank = false;
+        // Presses from the frame before have had a whole frame to be read
+        self.unread_keys &= self.frame_presses;
+        self.frame_presses = 0;
+        self.apply_freezes();
+        self.tick_timers();
+        #[cfg(feature = "std")]
+        if let Some(Err(e)) = self.tracer.as_mut().map(|tracer| tracer.flush()) {
+            log!("Could not write trace: {e}");
+            self.tracer = None;
+        }
+        true
+    }
+    /// State before the instruction that was just fetched runs
+    #[cfg(feature = "std")]
+    fn trace_record(&self, pc: u16, opcode: u16) -> TraceRecord {
+        TraceRecord {
+            cycle: self.debug_iter,
+            pc,
+            opcode,
+            registers: self.registers,
+            i: self.address,
+            sp: self.stack_ptr as u8,
+            dt: self.delay_timer,
+            st: self.sound_timer,
+            writes: Vec::new(),
+        }
+    }
 
     fn clear_display(&mut self) {
         self.vram = [false; WIDTH * HEIGHT];
@@ -92,81 +939,198 @@ impl Chip8Interpreter {
     fn draw_sprite(&mut self, x: usize, y: usize, n: usize) {
         self.registers[0xf] = 0;
         for byte in 0..n {
-            let y = (self.registers[y] as usize + byte) % HEIGHT;
+            // The starting position always wraps, the rest of the sprite may be clipped
+            let y = self.registers[y] as usize % HEIGHT + byte;
+            if y >= HEIGHT && self.quirks.clip_sprites {
+                break;
+            }
+            let y = y % HEIGHT;
+            let at = self.offset_from_i(byte);
+            let row = self.memory[at];
+            self.notify(|observer, machine| observer.sprite_row(machine, at as u16, row));
             for bit in 0..8 {
-                let x = (self.registers[x] as usize + bit as usize) % WIDTH;
-                let color = (self.memory[self.address as usize + byte] >> (7 - bit)) & 1;
+                let x = self.registers[x] as usize % WIDTH + bit as usize;
+                if x >= WIDTH && self.quirks.clip_sprites {
+                    break;
+                }
+                let x = x % WIDTH;
+                let color = (row >> (7 - bit)) & 1;
                 self.registers[0x0f] |= color & self.vram[y * WIDTH + x] as u8;
                 self.vram[y * WIDTH + x] ^= color != 0;
+                self.vram_since_present[y * WIDTH + x] |= self.vram[y * WIDTH + x];
             }
         }
     }
-    /// Redraws to pixel buffer at every frame
+    /// Redraws to pixel buffer at every frame, according to the display mode
     pub fn draw_pixels(&mut self, pixels: &mut [u8]) {
         debug_assert!(pixels.len() == HEIGHT * WIDTH * 4);
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                let state = match self.vram[y * WIDTH + x] {
-                    true => PIXEL_ON,
-                    false => PIXEL_OFF,
-                };
-                let index = (y * WIDTH + x) * 4;
-                pixels[index..index + 4].copy_from_slice(state);
+        for i in 0..WIDTH * HEIGHT {
+            let index = i * 4;
+            match self.display_mode {
+                DisplayMode::Persistence(frames) if frames > 0 => {
+                    if self.vram[i] {
+                        self.phosphor[i] = frames;
+                        pixels[index..index + 4].copy_from_slice(&self.palette.blend(255));
+                    } else {
+                        self.phosphor[i] = self.phosphor[i].saturating_sub(1);
+                        let level = (255 * self.phosphor[i] as u16 / frames as u16) as u8;
+                        pixels[index..index + 4].copy_from_slice(&self.palette.blend(level));
+                    }
+                }
+                DisplayMode::Vblank => {
+                    let level = self.vram_since_present[i] as u8 * 255;
+                    pixels[index..index + 4].copy_from_slice(&self.palette.blend(level));
+                }
+                _ => {
+                    let level = self.vram[i] as u8 * 255;
+                    pixels[index..index + 4].copy_from_slice(&self.palette.blend(level));
+                }
             }
         }
+        // Next present starts from what is currently on screen
+        self.vram_since_present = self.vram;
     }
-    /// Updates all timers
-    fn update_timer(&mut self) {
-        const TIMER_PERIOD: u16 = 1;
-        if self.delay_timer > 0 {
-            self.total_dt += TIMER_PERIOD;
-            while self.total_dt > TIMER_PERIOD {
-                self.total_dt -= TIMER_PERIOD;
-                self.delay_timer = self.delay_timer.wrapping_sub(1);
-            }
-        }
-        if self.sound_timer > 0 {
-            self.total_dt2 += TIMER_PERIOD;
-            while self.total_dt2 > TIMER_PERIOD {
-                self.total_dt2 -= TIMER_PERIOD;
-                self.sound_timer = self.sound_timer.wrapping_sub(1);
-            }
-        }
+    /// Counts both timers down once, called at `FRAME_RATE` by `step_frame`
+    pub fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.notify_sound();
     }
-    /// Fetches, decodes, and executes the instruction as well as updates timers
+    /// Fetches, decodes, and executes the instruction
     pub fn execute_cycle(&mut self) {
+        // Keys still change with nothing running, otherwise the queue would only grow
+        self.apply_key_events();
         if !self.should_execute {
             return;
         }
-        self.update_timer();
 
+        let pc = self.program_counter;
         let opcode = {
-            let location = self.program_counter as usize;
-            let mem = [self.memory[location], self.memory[location + 1]];
+            let location = pc as usize;
+            let mem = [self.memory[location], self.memory[(location + 1) & ADDRESS_MASK as usize]];
             u16::from_be_bytes(mem)
         };
+        self.notify(|observer, machine| observer.fetch(machine, pc, opcode));
 
-        self.program_counter += 2;
+        self.skip();
+        self.instructions = self.instructions.wrapping_add(1);
+        self.debug_iter = self.debug_iter.wrapping_add(1);
+
+        #[cfg(feature = "std")]
+        if self.tracer.is_some() {
+            let record = self.trace_record(pc, opcode);
+            if let Some(tracer) = &mut self.tracer {
+                tracer.begin(record);
+            }
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(pc, opcode, &self.memory, &self.stack[..self.stack_ptr]);
+        }
 
         self.debug(opcode);
 
+        self.notify(|observer, machine| observer.before_execute(machine, pc, opcode));
         self.handle_opcode(opcode);
+        self.notify(|observer, machine| observer.after_execute(machine, pc, opcode));
+        self.notify_sound();
+
+        #[cfg(feature = "std")]
+        if let Some(Err(e)) = self.tracer.as_mut().map(|tracer| tracer.finish()) {
+            log!("Could not write trace: {e}");
+            self.tracer = None;
+        }
+    }
+    /// Calls the coverage and the observer, if there are any, with the machine they are watching
+    #[inline]
+    fn notify(&mut self, event: impl Fn(&mut dyn Observer, &Self)) {
+        if let Some(mut coverage) = self.coverage.take() {
+            event(&mut coverage, self);
+            self.coverage = Some(coverage);
+        }
+        if let Some(mut observer) = self.observer.take() {
+            event(observer.as_mut(), self);
+            self.observer = Some(observer);
+        }
+    }
+    /// Tells the observer when `should_beep` changes
+    fn notify_sound(&mut self) {
+        if self.observer.is_some() && self.should_beep() != self.beeping {
+            self.beeping = !self.beeping;
+            let on = self.beeping;
+            self.notify(|observer, machine| observer.sound(machine, on));
+        }
     }
 
+    /// Progresses `Fx0A`, returning the key once it is done. Keys held before the wait began
+    /// have to be pressed again
+    fn wait_for_key(&mut self) -> Option<u8> {
+        self.unread_keys = 0;
+        let pressed = (0..16).find(|key| self.key_presses & (1 << key) != 0);
+        match (self.key_wait, pressed) {
+            (KeyWait::Idle, _) => {
+                self.key_presses = 0;
+                self.key_wait = KeyWait::Pressing;
+                None
+            }
+            (KeyWait::Pressing, Some(key)) if self.quirks.key_wait_on_press => {
+                self.key_wait = KeyWait::Idle;
+                Some(key)
+            }
+            (KeyWait::Pressing, Some(key)) => {
+                self.key_wait = KeyWait::Held(key);
+                self.wait_for_key()
+            }
+            (KeyWait::Pressing, None) => None,
+            (KeyWait::Held(key), _) if !self.keyboard[key as usize] => {
+                self.key_wait = KeyWait::Idle;
+                Some(key)
+            }
+            (KeyWait::Held(_), _) => None,
+        }
+    }
+    /// Moves PC past an instruction, wrapping around memory
+    fn skip(&mut self) {
+        self.program_counter = (self.program_counter + 2) & ADDRESS_MASK;
+    }
+    /// Address `offset` bytes past I, wrapping around memory
+    fn offset_from_i(&self, offset: usize) -> usize {
+        (self.address as usize + offset) & ADDRESS_MASK as usize
+    }
+    /// Writes for `Fx33` and `Fx55`, which leave the font alone unless the quirks allow it
+    fn store(&mut self, offset: usize, value: u8) {
+        let at = self.offset_from_i(offset);
+        if at >= FONT_SET.len() || self.quirks.writes_font {
+            self.memory[at] = value;
+            #[cfg(feature = "std")]
+            if let Some(tracer) = &mut self.tracer {
+                tracer.memory_write(at as u16, value);
+            }
+            self.notify(|observer, machine| observer.memory_write(machine, at as u16, value));
+        }
+    }
+    /// Stops running the ROM until it is reset
+    fn halt(&mut self, fault: Fault) {
+        if self.debug > 0 {
+            log!("Stopped: {fault}");
+        }
+        self.fault = Some(fault);
+        self.should_execute = false;
+    }
     /// Outputs important information for debugging, use `-d <LEVEL>` when running to enable
     fn debug(&mut self, opcode: u16) {
-        self.debug_iter = self.debug_iter.wrapping_add(1);
         if self.debug > 0 {
-            println!("[NEW ITERATION: {}]", self.debug_iter);
-            println!("Registers: {:?}", self.registers);
-            println!("Stack: {:?}", self.stack);
-            println!("Instruction: {:#4x}", opcode);
-            println!("Instruction ptr: {}", self.program_counter);
-            println!("Address: {}", self.address);
+            log!("[NEW ITERATION: {}]", self.debug_iter);
+            log!("Registers: {:?}", self.registers);
+            log!("Stack: {:?}", self.stack);
+            log!("Instruction: {:#4x}", opcode);
+            log!("Instruction ptr: {}", self.program_counter);
+            log!("Address: {}", self.address);
         }
         if self.debug > 1 {
-            println!("VRAM: {:?}", self.vram);
-            println!("Memory: {:?}", self.memory)
+            log!("VRAM: {:?}", self.vram);
+            // Only the rows around the next instruction and I, the whole memory is too much to follow
+            let pc = self.program_counter as usize;
+            log!("Memory:\n{}{}", hex_view(self, pc, 2, ROW_BYTES), hex_view(self, self.address as usize, ROW_BYTES * 2, ROW_BYTES));
         }
     }
     /// Decodes and executes instruction
@@ -174,7 +1138,7 @@ impl Chip8Interpreter {
         let x = ((opcode & 0x0F00) >> 8) as usize;
         let y = ((opcode & 0x00F0) >> 4) as usize;
         let byte = opcode & 0x00FF;
-        let address = opcode & 0x07FF;
+        let address = opcode & ADDRESS_MASK;
         let nimble = opcode & 0x000F;
         // Matches opcode from this macro, code defined in `chip8-macros/lib`
         // See references in readme for more information about each instruction
@@ -184,6 +1148,10 @@ impl Chip8Interpreter {
                 self.clear_display()
             },
             "00ee" => {
+                if self.stack_ptr == 0 {
+                    self.halt(Fault::StackUnderflow { pc: self.program_counter.wrapping_sub(2) & ADDRESS_MASK });
+                    return;
+                }
                 self.stack_ptr -= 1;
                 self.program_counter = self.stack[self.stack_ptr];
             },
@@ -191,23 +1159,27 @@ impl Chip8Interpreter {
                 self.program_counter = address;
             },
             "2nnn" => {
+                if self.stack_ptr == self.stack.len() {
+                    self.halt(Fault::StackOverflow { pc: self.program_counter.wrapping_sub(2) & ADDRESS_MASK });
+                    return;
+                }
                 self.stack[self.stack_ptr] = self.program_counter;
                 self.stack_ptr += 1;
                 self.program_counter = address;
             },
             "3xkk" => {
                 if self.registers[x] as u16 == byte {
-                    self.program_counter += 2;
+                    self.skip();
                 }
             },
             "4xkk" => {
                 if self.registers[x] as u16 != byte {
-                    self.program_counter += 2;
+                    self.skip();
                 }
             },
             "5xy0" => {
                 if self.registers[x] == self.registers[y] {
-                    self.program_counter += 2;
+                    self.skip();
                 }
             },
             "6xkk" => {
@@ -221,12 +1193,21 @@ impl Chip8Interpreter {
             },
             "8xy1" => {
                 self.registers[x] |= self.registers[y];
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xf] = 0;
+                }
             },
             "8xy2" => {
                 self.registers[x] &= self.registers[y];
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xf] = 0;
+                }
             },
             "8xy3" => {
                 self.registers[x] ^= self.registers[y];
+                if self.quirks.logic_resets_vf {
+                    self.registers[0xf] = 0;
+                }
             },
             "8xy4" => {
                 let intermediate = self.registers[x] as u16 + self.registers[y] as u16;
@@ -234,86 +1215,234 @@ impl Chip8Interpreter {
                 self.registers[x] = (intermediate & 0xff) as u8;
             },
             "8xy5" => {
-                self.registers[0xf] = (self.registers[x] > self.registers[y]) as u8;
+                self.registers[0xf] = (self.registers[x] >= self.registers[y]) as u8;
                 self.registers[x] = self.registers[x].wrapping_sub(self.registers[y]);
             },
             "8xy6" => {
-                self.registers[0xf] = self.registers[x] & 0x1;
-                self.registers[x] /= 2;
+                let source = self.registers[if self.quirks.shift_uses_vy { y } else { x }];
+                self.registers[x] = source >> 1;
+                self.registers[0xf] = source & 0x1;
             },
             "8xy7" => {
-                self.registers[0xf] = (self.registers[y] > self.registers[x]) as u8;
+                self.registers[0xf] = (self.registers[y] >= self.registers[x]) as u8;
                 self.registers[x] = self.registers[y].wrapping_sub(self.registers[x]);
             },
             "8xyE" => {
-                self.registers[0xf] = self.registers[x] >> 7;
-                self.registers[x] *= 2;
+                let source = self.registers[if self.quirks.shift_uses_vy { y } else { x }];
+                self.registers[x] = source << 1;
+                self.registers[0xf] = source >> 7;
             },
             "9xy0" => {
                 if self.registers[x] != self.registers[y] {
-                    self.program_counter += 2;
+                    self.skip();
                 }
             },
             "Annn" => {
                 self.address = address;
             },
             "Bnnn" => {
-                self.program_counter = address + self.registers[0x0] as u16;
+                let offset = self.registers[if self.quirks.jump_uses_vx { x } else { 0 }];
+                self.program_counter = (address + offset as u16) & ADDRESS_MASK;
             },
             "Cxkk" => {
-                self.registers[x] = self.rng.u8(0..255) & byte as u8;
+                self.registers[x] = self.rng.u8(..) & byte as u8;
             },
             "Dxyn" => {
+                // Taken before drawing, which can overwrite either register with the collision flag
+                let (at_x, at_y, sprite) = (self.registers[x], self.registers[y], self.address);
                 self.draw_sprite(x, y, nimble as usize);
+                let collision = self.registers[0xF] != 0;
+                self.notify(|observer, machine| observer.sprite_draw(machine, at_x, at_y, sprite, nimble as u8, collision));
+                self.waiting_for_vblank = self.quirks.display_wait || self.timing == Timing::Vip;
             },
             "Ex9E" => {
-                if self.keyboard[self.registers[x] as usize] {
-                    self.program_counter += 2;
+                if self.read_key(self.registers[x] as usize & 0xF) {
+                    self.skip();
                 }
             },
             "ExA1" => {
-                if !self.keyboard[self.registers[x] as usize] {
-                    self.program_counter += 2;
+                if !self.read_key(self.registers[x] as usize & 0xF) {
+                    self.skip();
                 }
             },
             "Fx07" => {
                 self.registers[x] = self.delay_timer;
             },
             "Fx0A" => {
-                if let Some(key) = self.keyboard.iter().position(|x| *x) {
-                    self.registers[x] = key as u8;
+                if self.key_wait == KeyWait::Idle {
+                    self.notify(|observer, machine| observer.key_wait(machine, x as u8));
+                }
+                if let Some(key) = self.wait_for_key() {
+                    self.registers[x] = key;
+                    self.notify(|observer, machine| observer.key_wait_done(machine, key));
                 } else {
                     // Rerun this instruction
-                    self.program_counter -= 2;
+                    self.program_counter = self.program_counter.wrapping_sub(2) & ADDRESS_MASK;
                 }
             },
             "Fx15" => {
-                self.delay_timer = self.registers[x] + 1;
+                self.delay_timer = self.registers[x];
             },
             "Fx18" => {
                 self.sound_timer = self.registers[x];
             },
             "Fx1E" => {
-                self.address += self.registers[x] as u16;
+                self.address = self.address.wrapping_add(self.registers[x] as u16);
             },
             "Fx29" => {
-                self.address = self.registers[x] as u16 * 5
+                // Only the low digit picks a character, so I stays within the font
+                self.address = (self.registers[x] & 0xF) as u16 * 5
             },
             "Fx33" => {
-                self.memory[self.address as usize] = self.registers[x] / 100;
-                self.memory[self.address as usize + 1] = (self.registers[x] / 10) % 10;
-                self.memory[self.address as usize + 2] = self.registers[x] % 10;
+                let value = self.registers[x];
+                self.store(0, value / 100);
+                self.store(1, (value / 10) % 10);
+                self.store(2, value % 10);
             },
             "Fx55" => {
-                let mem = &mut self.memory[address as usize..address as usize + x];
-                mem.copy_from_slice(&self.registers[0..x]);
-                self.address += (x + 1) as u16;
+                for n in 0..=x {
+                    self.store(n, self.registers[n]);
+                }
+                if self.quirks.load_store_increments_i {
+                    self.address = self.address.wrapping_add((x + 1) as u16);
+                }
             },
             "Fx65" => {
-                let mem = &mut self.memory[address as usize..address as usize + x];
-                self.registers[0..x].copy_from_slice(mem);
-                self.address += (x + 1) as u16;
+                for n in 0..=x {
+                    let at = self.offset_from_i(n);
+                    self.registers[n] = self.memory[at];
+                    self.notify(|observer, machine| observer.memory_read(machine, at as u16, machine.memory[at]));
+                }
+                if self.quirks.load_store_increments_i {
+                    self.address = self.address.wrapping_add((x + 1) as u16);
+                }
             }
         );
     }
 }
+
+/// Sets up a machine in any state, like just before the instruction a test is about. Memory starts with
+/// the font and the ROM, then the given bytes are written over it
+#[derive(Clone, Debug)]
+pub struct Chip8InterpreterBuilder {
+    rom: Option<Vec<u8>>,
+    quirks: Quirks,
+    timing: Timing,
+    seed: Option<u64>,
+    registers: [u8; 16],
+    index: u16,
+    program_counter: u16,
+    stack: Vec<u16>,
+    delay_timer: u8,
+    sound_timer: u8,
+    // Written in order once the ROM is loaded
+    memory: Vec<(u16, Vec<u8>)>,
+}
+
+impl Default for Chip8InterpreterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Chip8InterpreterBuilder {
+    /// The same machine as `Chip8Interpreter::new`, but ready to run from 0x200 without a ROM
+    pub fn new() -> Self {
+        Self {
+            rom: None,
+            quirks: Quirks::MODERN,
+            timing: Timing::Instructions,
+            seed: None,
+            registers: [0; 16],
+            index: 0,
+            program_counter: 0x200,
+            stack: Vec::new(),
+            delay_timer: 0,
+            sound_timer: 0,
+            memory: Vec::new(),
+        }
+    }
+    /// Loaded at 0x200 and kept for resets
+    pub fn with_rom(mut self, rom: &[u8]) -> Self {
+        self.rom = Some(rom.to_vec());
+        self
+    }
+
+    pub fn with_quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    pub fn with_timing(mut self, timing: Timing) -> Self {
+        self.timing = timing;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+    /// V0 to VF
+    pub fn with_registers(mut self, registers: [u8; 16]) -> Self {
+        self.registers = registers;
+        self
+    }
+    /// Vx, `x` is taken modulo 16
+    pub fn with_register(mut self, x: usize, value: u8) -> Self {
+        self.registers[x & 0xF] = value;
+        self
+    }
+    /// I, only the low 12 bits are kept
+    pub fn with_index(mut self, index: u16) -> Self {
+        self.index = index;
+        self
+    }
+    /// Only the low 12 bits are kept
+    pub fn with_program_counter(mut self, address: u16) -> Self {
+        self.program_counter = address;
+        self
+    }
+    /// Return addresses, the innermost last, kept like `Chip8Interpreter::set_stack` keeps them
+    pub fn with_stack(mut self, stack: &[u16]) -> Self {
+        self.stack = stack.to_vec();
+        self
+    }
+
+    pub fn with_delay_timer(mut self, value: u8) -> Self {
+        self.delay_timer = value;
+        self
+    }
+
+    pub fn with_sound_timer(mut self, value: u8) -> Self {
+        self.sound_timer = value;
+        self
+    }
+    /// Writes bytes from an address on, wrapping around the end of memory. Can be used more than once
+    pub fn with_memory(mut self, address: u16, bytes: &[u8]) -> Self {
+        self.memory.push((address, bytes.to_vec()));
+        self
+    }
+
+    pub fn build(self) -> Chip8Interpreter {
+        let mut interpreter = Chip8Interpreter::new();
+        interpreter.set_quirks(self.quirks);
+        interpreter.set_timing(self.timing);
+        if let Some(seed) = self.seed {
+            interpreter.set_seed(seed);
+        }
+        match &self.rom {
+            Some(rom) => interpreter.load_bytes(rom),
+            None => interpreter.should_execute = true,
+        }
+        for (address, bytes) in &self.memory {
+            interpreter.write_bytes(*address, bytes);
+        }
+        interpreter.registers = self.registers;
+        interpreter.set_index(self.index);
+        interpreter.set_program_counter(self.program_counter);
+        interpreter.set_stack(&self.stack);
+        interpreter.delay_timer = self.delay_timer;
+        interpreter.sound_timer = self.sound_timer;
+        interpreter
+    }
+}
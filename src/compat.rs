@@ -0,0 +1,275 @@
+use std::{
+    collections::BTreeSet,
+    fmt::{self, Write},
+    fs, io,
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+use crate::chip8::{Chip8Interpreter, Fault, FRAME_RATE};
+use crate::disasm::pattern;
+use crate::menu::is_rom;
+use crate::quirks::Quirks;
+
+/// A run whose last second of instructions only touched this many addresses is stuck
+const STUCK_ADDRESSES: usize = 4;
+/// Name of the threads `check_all` runs ROMs on, so a panic hook can tell their panics apart
+pub const WORKER_THREAD: &str = "compat";
+
+/// How a ROM fared under one quirks preset
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Ran until the end
+    Ok,
+    /// Never drew anything and ended up going round the same few instructions
+    Hang,
+    /// Stopped at an instruction this interpreter does not know
+    UnknownOpcode { pc: u16, opcode: u16 },
+    /// The interpreter stopped the ROM, like on a stack overflow
+    Fault(Fault),
+    /// The interpreter panicked
+    Crash(String),
+}
+
+impl Outcome {
+    pub fn is_ok(&self) -> bool {
+        *self == Outcome::Ok
+    }
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Outcome::Ok => write!(f, "ok"),
+            Outcome::Hang => write!(f, "hang"),
+            Outcome::UnknownOpcode { pc, opcode } => write!(f, "unknown opcode {opcode:04X} at {pc:03X}"),
+            Outcome::Fault(fault) => write!(f, "{fault}"),
+            Outcome::Crash(message) => write!(f, "crash: {message}"),
+        }
+    }
+}
+
+/// One ROM run under one preset
+#[derive(Clone, Debug)]
+pub struct Report {
+    pub rom: PathBuf,
+    pub preset: &'static str,
+    pub outcome: Outcome,
+    pub frames: u32,
+    pub instructions: u64,
+    /// `Dxyn` and `00E0` run
+    pub draws: u64,
+    /// `vram` when the run ended
+    pub screen: Vec<bool>,
+}
+
+/// Runs a ROM without a display for a number of frames, stopping early at the first problem. A
+/// panic is caught and reported as a crash, but the panic hook still runs for it
+pub fn check(rom: &Path, bytes: &[u8], preset: &'static str, frames: u32) -> Report {
+    let mut report = Report {
+        rom: rom.to_path_buf(),
+        preset,
+        outcome: Outcome::Ok,
+        frames: 0,
+        instructions: 0,
+        draws: 0,
+        screen: Vec::new(),
+    };
+    let mut interpreter = Chip8Interpreter::new();
+    interpreter.set_quirks(Quirks::preset(preset).unwrap_or_default());
+    interpreter.set_seed(0);
+    interpreter.load_bytes(bytes);
+    // The interpreter is left as it was when a panic interrupted it, which is fine for a screenshot
+    let result = panic::catch_unwind(AssertUnwindSafe(|| run(&mut interpreter, &mut report, frames)));
+    report.outcome = match result {
+        Ok(outcome) => outcome,
+        Err(payload) => Outcome::Crash(match (payload.downcast_ref::<&str>(), payload.downcast_ref::<String>()) {
+            (Some(message), _) => message.to_string(),
+            (_, Some(message)) => message.clone(),
+            _ => String::from("unknown panic"),
+        }),
+    };
+    report.instructions = interpreter.instruction_count();
+    report.screen = interpreter.vram().to_vec();
+    report
+}
+
+fn run(interpreter: &mut Chip8Interpreter, report: &mut Report, frames: u32) -> Outcome {
+    // Addresses run in the last second
+    let mut recent = BTreeSet::new();
+    for frame in 0..frames {
+        if frame % FRAME_RATE == 0 {
+            recent.clear();
+        }
+        loop {
+            let pc = interpreter.program_counter();
+            let memory = interpreter.memory();
+            let opcode = u16::from_be_bytes([memory[pc as usize], memory[(pc as usize + 1) % memory.len()]]);
+            match pattern(opcode) {
+                None | Some("0nnn") => return Outcome::UnknownOpcode { pc, opcode },
+                Some("Dxyn" | "00E0") => report.draws += 1,
+                _ => {}
+            }
+            if recent.len() <= STUCK_ADDRESSES {
+                recent.insert(pc);
+            }
+            let frame_done = interpreter.step_instruction();
+            if let Some(fault) = interpreter.fault() {
+                return Outcome::Fault(fault);
+            }
+            if frame_done {
+                break;
+            }
+        }
+        report.frames = frame + 1;
+    }
+    match report.draws == 0 && recent.len() <= STUCK_ADDRESSES {
+        true => Outcome::Hang,
+        false => Outcome::Ok,
+    }
+}
+
+/// ROMs in a directory sorted by name
+pub fn find_roms(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut roms: Vec<PathBuf> = fs::read_dir(dir)?
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| is_rom(path))
+        .collect();
+    roms.sort();
+    Ok(roms)
+}
+
+/// Checks every ROM under every preset on all cores, in the order of `roms` then `Quirks::PRESETS`.
+/// ROMs that cannot be read are left out. The runs happen on threads named `WORKER_THREAD`
+pub fn check_all(roms: &[PathBuf], frames: u32) -> Vec<Report> {
+    let roms: Vec<(&PathBuf, Vec<u8>)> = roms
+        .iter()
+        .filter_map(|path| fs::read(path).ok().map(|bytes| (path, bytes)))
+        .collect();
+    let jobs: Vec<(usize, &'static str)> = (0..roms.len())
+        .flat_map(|rom| Quirks::PRESETS.into_iter().map(move |preset| (rom, preset)))
+        .collect();
+    let reports: Vec<Mutex<Option<Report>>> = jobs.iter().map(|_| Mutex::new(None)).collect();
+    let next = AtomicUsize::new(0);
+    let workers = thread::available_parallelism().map_or(1, |n| n.get()).min(jobs.len());
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let worker = thread::Builder::new().name(String::from(WORKER_THREAD));
+            let spawned = worker.spawn_scoped(scope, || loop {
+                let job = next.fetch_add(1, Ordering::Relaxed);
+                let Some((rom, preset)) = jobs.get(job) else {
+                    break;
+                };
+                let (path, bytes) = &roms[*rom];
+                let report = check(path, bytes, preset, frames);
+                *reports[job].lock().unwrap() = Some(report);
+            });
+            spawned.expect("could not start a worker thread");
+        }
+    });
+    reports
+        .into_iter()
+        .filter_map(|report| report.into_inner().unwrap())
+        .collect()
+}
+
+/// File name to save a report's screenshot as, unique for each ROM file and preset. Characters
+/// that are not safe in file names are replaced, so a hash of the whole path tells apart names
+/// that end up the same
+pub fn screenshot_name(report: &Report) -> String {
+    let name = report.rom.file_name().unwrap_or_default().to_string_lossy();
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' { c } else { '_' })
+        .collect();
+    // 32 bit FNV-1a, short and the same on every run and platform
+    let hash = report.rom.as_os_str().as_encoded_bytes().iter().fold(0x811C_9DC5_u32, |hash, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+    });
+    format!("{name}-{hash:08x}-{}.png", report.preset)
+}
+
+/// Table of ROMs against presets with outcomes and screenshots from `screenshots`
+pub fn markdown(reports: &[Report], screenshots: &str) -> String {
+    let mut table = String::from("| ROM |");
+    let mut rule = String::from("| --- |");
+    for preset in Quirks::PRESETS {
+        let _ = write!(table, " {preset} |");
+        rule.push_str(" --- |");
+    }
+    let _ = write!(table, "\n{rule}\n");
+    for row in reports.chunks(Quirks::PRESETS.len()) {
+        let _ = write!(table, "| {} |", row[0].rom.file_name().unwrap_or_default().to_string_lossy());
+        for report in row {
+            let _ = write!(
+                table,
+                " {}<br>{} frames, {} draws<br>![]({screenshots}/{}) |",
+                report.outcome,
+                report.frames,
+                report.draws,
+                screenshot_name(report),
+            );
+        }
+        table.push('\n');
+    }
+    let _ = writeln!(table, "\n{}", summary(reports));
+    table
+}
+
+/// Same table as `markdown` as a standalone page, with failures highlighted
+pub fn html(reports: &[Report], screenshots: &str) -> String {
+    let mut page = String::from(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>CHIP-8 compatibility</title>\n<style>\n\
+         body { font-family: sans-serif; }\n\
+         td, th { border: 1px solid #ccc; padding: 4px; vertical-align: top; }\n\
+         td.ok { background: #e6f4e6; }\n\
+         td.fail { background: #f8e0e0; }\n\
+         img { image-rendering: pixelated; display: block; }\n\
+         </style>\n</head>\n<body>\n<table>\n<tr><th>ROM</th>",
+    );
+    for preset in Quirks::PRESETS {
+        let _ = write!(page, "<th>{preset}</th>");
+    }
+    page.push_str("</tr>\n");
+    for row in reports.chunks(Quirks::PRESETS.len()) {
+        let rom = row[0].rom.file_name().unwrap_or_default().to_string_lossy();
+        let _ = write!(page, "<tr><td>{}</td>", escape(&rom));
+        for report in row {
+            let _ = write!(
+                page,
+                "<td class=\"{}\">{}<br>{} frames, {} draws<img src=\"{screenshots}/{}\"></td>",
+                if report.outcome.is_ok() { "ok" } else { "fail" },
+                escape(&report.outcome.to_string()),
+                report.frames,
+                report.draws,
+                screenshot_name(report),
+            );
+        }
+        page.push_str("</tr>\n");
+    }
+    let _ = write!(page, "</table>\n<p>{}</p>\n</body>\n</html>\n", escape(&summary(reports)));
+    page
+}
+
+/// How many runs of each preset were ok
+fn summary(reports: &[Report]) -> String {
+    let counts: Vec<String> = Quirks::PRESETS
+        .into_iter()
+        .map(|preset| {
+            let runs = reports.iter().filter(|report| report.preset == preset);
+            let ok = runs.clone().filter(|report| report.outcome.is_ok()).count();
+            format!("{preset} {ok}/{}", runs.count())
+        })
+        .collect();
+    format!("Ok: {}", counts.join(", "))
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
@@ -0,0 +1,189 @@
+use alloc::{format, string::String, vec, vec::Vec};
+use core::{fmt::Write, ops::Range};
+
+use crate::chip8::{Chip8Interpreter, ADDRESS_MASK};
+use crate::observer::Observer;
+
+/// Where ROMs are loaded, bytes before it hold the interpreter and font
+const ROM_START: usize = 0x200;
+/// Bytes on each row of the heatmap
+pub const HEATMAP_COLUMNS: usize = 64;
+
+type Filter = fn(&Access) -> bool;
+
+/// Ways a byte of memory was used and how many times
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Access {
+    /// Fetched as half of an instruction
+    pub executed: u32,
+    /// Drawn by `Dxyn`
+    pub sprite: u32,
+    /// Loaded into registers by `Fx65`
+    pub read: u32,
+    /// Stored to by `Fx33` or `Fx55`
+    pub written: u32,
+}
+
+impl Access {
+    /// Both run as code and changed by the program, so a disassembly of the ROM is not what ran
+    pub fn self_modifying(&self) -> bool {
+        self.executed > 0 && self.written > 0
+    }
+
+    pub fn touched(&self) -> bool {
+        self.total() > 0
+    }
+
+    fn total(&self) -> u64 {
+        self.executed as u64 + self.sprite as u64 + self.read as u64 + self.written as u64
+    }
+}
+
+/// Records which bytes of memory were run, drawn, read and written. The interpreter reports every
+/// access to the one set with `set_coverage`, as it would to an observer
+#[derive(Clone, Debug)]
+pub struct Coverage {
+    accesses: Vec<Access>,
+}
+
+impl Default for Coverage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self { accesses: vec![Access::default(); 4096] }
+    }
+    fn mark(&mut self, address: u16, count: impl Fn(&mut Access) -> &mut u32) {
+        let count = count(&mut self.accesses[(address & ADDRESS_MASK) as usize]);
+        *count = count.saturating_add(1);
+    }
+
+    pub fn access(&self, address: u16) -> Access {
+        self.accesses.get(address as usize).copied().unwrap_or_default()
+    }
+    /// Inclusive ranges of addresses in `within` that match
+    pub fn ranges(&self, within: Range<usize>, filter: impl Fn(&Access) -> bool) -> Vec<(u16, u16)> {
+        let mut ranges: Vec<(u16, u16)> = Vec::new();
+        for (address, access) in self.accesses.iter().enumerate().take(within.end).skip(within.start) {
+            if !filter(access) {
+                continue;
+            }
+            match ranges.last_mut() {
+                Some((_, end)) if *end as usize + 1 == address => *end = address as u16,
+                _ => ranges.push((address as u16, address as u16)),
+            }
+        }
+        ranges
+    }
+    /// Bytes used in each way, and bytes of the ROM that were never used, as a JSON object
+    pub fn json(&self, rom_len: usize) -> String {
+        let memory = 0..self.accesses.len();
+        let kinds: [(&str, Range<usize>, Filter); 6] = [
+            ("executed", memory.clone(), |access| access.executed > 0),
+            ("sprite", memory.clone(), |access| access.sprite > 0),
+            ("read", memory.clone(), |access| access.read > 0),
+            ("written", memory.clone(), |access| access.written > 0),
+            ("self_modifying", memory, Access::self_modifying),
+            ("untouched", ROM_START..ROM_START + rom_len, |access| !access.touched()),
+        ];
+        let mut json = format!("{{\n  \"rom\": {{\"start\": {ROM_START}, \"length\": {rom_len}}},\n");
+        let mut bytes = Vec::new();
+        for (name, within, filter) in kinds {
+            let ranges = self.ranges(within, filter);
+            let listed: Vec<String> = ranges.iter().map(|(start, end)| format!("[{start}, {end}]")).collect();
+            let _ = writeln!(json, "  \"{name}\": [{}],", listed.join(", "));
+            let count: usize = ranges.iter().map(|(start, end)| (end - start) as usize + 1).sum();
+            bytes.push(format!("\"{name}\": {count}"));
+        }
+        let _ = writeln!(json, "  \"bytes\": {{{}}}\n}}", bytes.join(", "));
+        json
+    }
+    /// Bytes of the ROM that were used in each way
+    pub fn summary(&self, rom_len: usize) -> String {
+        let rom = &self.accesses[ROM_START..(ROM_START + rom_len).min(self.accesses.len())];
+        let count = |filter: fn(&Access) -> bool| rom.iter().filter(|access| filter(access)).count();
+        let share = |count: usize| 100.0 * count as f64 / rom.len().max(1) as f64;
+        let mut summary = format!("{} ROM bytes\n", rom.len());
+        for (name, count) in [
+            ("executed", count(|access| access.executed > 0)),
+            ("sprite", count(|access| access.sprite > 0)),
+            ("read", count(|access| access.read > 0)),
+            ("written", count(|access| access.written > 0)),
+            ("self-modifying", count(Access::self_modifying)),
+            ("untouched", count(|access| !access.touched())),
+        ] {
+            let _ = writeln!(summary, "  {name:<15} {count:>5} {:5.1}%", share(count));
+        }
+        summary
+    }
+    /// RGB pixels of memory `HEATMAP_COLUMNS` bytes to a row with each byte `scale` pixels wide.
+    /// Self-modifying bytes are magenta, executed green, written red, sprites blue, read yellow and
+    /// untouched ROM grey, brighter the more they were used
+    pub fn heatmap(&self, rom_len: usize, scale: usize) -> Vec<u8> {
+        let scale = scale.max(1);
+        let rom = ROM_START..ROM_START + rom_len;
+        let most = self.accesses.iter().map(Access::total).max().unwrap_or_default().max(1);
+        let colours: Vec<[u8; 3]> = self
+            .accesses
+            .iter()
+            .enumerate()
+            .map(|(address, access)| {
+                let colour = if access.self_modifying() {
+                    [255, 0, 255]
+                } else if access.executed > 0 {
+                    [0, 220, 80]
+                } else if access.written > 0 {
+                    [240, 40, 40]
+                } else if access.sprite > 0 {
+                    [40, 120, 255]
+                } else if access.read > 0 {
+                    [240, 200, 0]
+                } else if rom.contains(&address) {
+                    return [64, 64, 64];
+                } else {
+                    return [0, 0, 0];
+                };
+                // Log scale so a byte run once is still visible next to a hot loop
+                let level = 96 + 159 * (access.total().ilog2() + 1) / (most.ilog2() + 1);
+                colour.map(|channel| (channel as u32 * level / 255) as u8)
+            })
+            .collect();
+        let mut pixels = Vec::with_capacity(colours.len() * scale * scale * 3);
+        for row in colours.chunks(HEATMAP_COLUMNS) {
+            for _ in 0..scale {
+                for colour in row {
+                    for _ in 0..scale {
+                        pixels.extend_from_slice(colour);
+                    }
+                }
+            }
+        }
+        pixels
+    }
+    /// Height in pixels of the heatmap at a scale
+    pub fn heatmap_height(&self, scale: usize) -> usize {
+        self.accesses.len().div_ceil(HEATMAP_COLUMNS) * scale.max(1)
+    }
+}
+
+impl Observer for Coverage {
+    fn fetch(&mut self, _: &Chip8Interpreter, pc: u16, _: u16) {
+        self.mark(pc, |access| &mut access.executed);
+        self.mark(pc.wrapping_add(1), |access| &mut access.executed);
+    }
+
+    fn memory_read(&mut self, _: &Chip8Interpreter, address: u16, _: u8) {
+        self.mark(address, |access| &mut access.read);
+    }
+
+    fn memory_write(&mut self, _: &Chip8Interpreter, address: u16, _: u8) {
+        self.mark(address, |access| &mut access.written);
+    }
+
+    fn sprite_row(&mut self, _: &Chip8Interpreter, address: u16, _: u8) {
+        self.mark(address, |access| &mut access.sprite);
+    }
+}
@@ -0,0 +1,603 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use serde_json::{json, Value};
+
+use crate::chip8::{Chip8Interpreter, FRAME_RATE};
+use crate::debugger::Debugger;
+use crate::disasm::disassemble;
+use crate::quirks::Quirks;
+use crate::settings::{Session, SettingsFile};
+use crate::timing::Timing;
+
+/// The interpreter is reported to clients as a single thread
+const THREAD_ID: u64 = 1;
+/// `variablesReference` of the registers scope
+const REGISTERS: u64 = 1;
+
+/// Addresses and the source lines they were assembled from
+#[derive(Clone, Debug, Default)]
+pub struct SymbolMap {
+    lines: Vec<(u16, PathBuf, u64)>,
+}
+
+impl SymbolMap {
+    /// Reads a map with a line of `ADDRESS FILE:LINE` for every instruction, the address is hex and
+    /// the file is relative to the map. Blank lines and lines starting with `#` are skipped
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        Ok(Self::parse(&text, path.parent().unwrap_or(Path::new(""))))
+    }
+
+    pub fn parse(text: &str, base: &Path) -> Self {
+        let lines = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let (address, location) = line.split_once(char::is_whitespace)?;
+                let (file, number) = location.trim().rsplit_once(':')?;
+                let address = u16::from_str_radix(address.trim_start_matches("0x"), 16).ok()?;
+                Some((address, normalise(&base.join(file)), number.parse().ok()?))
+            })
+            .collect();
+        Self { lines }
+    }
+    /// Source file and line an instruction came from
+    pub fn source(&self, address: u16) -> Option<(&Path, u64)> {
+        self.lines
+            .iter()
+            .find(|(at, ..)| *at == address)
+            .map(|(_, file, line)| (file.as_path(), *line))
+    }
+    /// Address of the first instruction on the line, or on the closest line after it with any.
+    /// Also returns the line that was found
+    pub fn address(&self, file: &Path, line: u64) -> Option<(u16, u64)> {
+        let file = normalise(file);
+        self.lines
+            .iter()
+            .filter(|(_, path, at)| *path == file && *at >= line)
+            .min_by_key(|(address, _, at)| (*at, *address))
+            .map(|(address, _, at)| (*address, *at))
+    }
+}
+
+fn normalise(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// What to do until the program next stops
+#[derive(Clone, Copy, Debug)]
+enum Run {
+    Continue,
+    StepIn,
+    /// Runs a called subroutine to completion
+    StepOver { depth: usize, to: u16 },
+    StepOut { depth: usize },
+}
+
+/// Debug Adapter Protocol session for a single client
+pub struct Server {
+    output: Box<dyn Write + Send>,
+    seq: u64,
+    interpreter: Chip8Interpreter,
+    debugger: Debugger,
+    symbols: SymbolMap,
+    // Lines asked for in each source file, resolved against the symbols whenever either changes
+    source_lines: HashMap<PathBuf, Vec<u64>>,
+    source_breakpoints: Vec<u16>,
+    instruction_breakpoints: Vec<u16>,
+    function_breakpoints: Vec<u16>,
+    launched: bool,
+    configured: bool,
+    started: bool,
+    stop_on_entry: bool,
+    running: Option<Run>,
+    // The instruction stopped at runs without hitting its own breakpoint again
+    leaving_breakpoint: bool,
+    next_frame: Instant,
+}
+
+/// Serves one client over stdin and stdout
+pub fn serve_stdio() -> io::Result<()> {
+    Server::new(Box::new(io::stdout())).run(io::stdin())
+}
+
+/// Waits for one client to connect to a port on localhost and serves it
+pub fn serve_tcp(port: u16) -> io::Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    eprintln!("Waiting for a debugger on port {}", listener.local_addr()?.port());
+    let (stream, _) = listener.accept()?;
+    Server::new(Box::new(stream.try_clone()?)).run(stream)
+}
+
+impl Server {
+    pub fn new(output: Box<dyn Write + Send>) -> Self {
+        Self {
+            output,
+            seq: 0,
+            interpreter: Chip8Interpreter::new(),
+            debugger: Debugger::default(),
+            symbols: SymbolMap::default(),
+            source_lines: HashMap::new(),
+            source_breakpoints: Vec::new(),
+            instruction_breakpoints: Vec::new(),
+            function_breakpoints: Vec::new(),
+            launched: false,
+            configured: false,
+            started: false,
+            stop_on_entry: false,
+            running: None,
+            leaving_breakpoint: false,
+            next_frame: Instant::now(),
+        }
+    }
+    /// Handles requests until the client disconnects, running the program in real time between them
+    pub fn run(mut self, input: impl Read + Send + 'static) -> io::Result<()> {
+        let (sender, messages) = mpsc::channel();
+        thread::spawn(move || {
+            let mut input = BufReader::new(input);
+            while let Some(message) = read_message(&mut input) {
+                if sender.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        let frame_length = Duration::from_secs(1) / FRAME_RATE;
+        loop {
+            let message = match self.running {
+                Some(_) => match messages.recv_timeout(self.next_frame.saturating_duration_since(Instant::now())) {
+                    Ok(message) => Some(message),
+                    Err(RecvTimeoutError::Timeout) => None,
+                    Err(RecvTimeoutError::Disconnected) => return Ok(()),
+                },
+                None => match messages.recv() {
+                    Ok(message) => Some(message),
+                    Err(_) => return Ok(()),
+                },
+            };
+            match message {
+                Some(message) if message["type"] == "request" => {
+                    if !self.handle(&message)? {
+                        return Ok(());
+                    }
+                }
+                Some(_) => {}
+                None => {
+                    self.run_frame()?;
+                    // Skips frames that could not keep up instead of rushing to catch up
+                    self.next_frame = (self.next_frame + frame_length).max(Instant::now());
+                }
+            }
+        }
+    }
+    /// Answers a request, returns false once the client is done
+    fn handle(&mut self, request: &Value) -> io::Result<bool> {
+        let command = request["command"].as_str().unwrap_or_default();
+        let args = &request["arguments"];
+        let result = match command {
+            "initialize" => Ok(json!({
+                "supportsConfigurationDoneRequest": true,
+                "supportsFunctionBreakpoints": true,
+                "supportsInstructionBreakpoints": true,
+                "supportsReadMemoryRequest": true,
+                "supportsDisassembleRequest": true,
+                "supportsEvaluateForHovers": false,
+                "supportsTerminateRequest": true,
+            })),
+            "launch" => self.launch(args),
+            "setBreakpoints" => Ok(self.set_breakpoints(args)),
+            "setInstructionBreakpoints" => Ok(self.set_instruction_breakpoints(args)),
+            "setFunctionBreakpoints" => Ok(self.set_function_breakpoints(args)),
+            "configurationDone" => {
+                self.configured = true;
+                Ok(Value::Null)
+            }
+            "threads" => Ok(json!({ "threads": [{ "id": THREAD_ID, "name": "CHIP-8" }] })),
+            "stackTrace" => Ok(self.stack_trace()),
+            "scopes" => Ok(json!({ "scopes": [{
+                "name": "Registers",
+                "presentationHint": "registers",
+                "variablesReference": REGISTERS,
+                "expensive": false,
+            }] })),
+            "variables" => Ok(self.variables(args)),
+            "continue" => {
+                self.resume(Run::Continue);
+                Ok(json!({ "allThreadsContinued": true }))
+            }
+            "next" => {
+                let pc = self.interpreter.program_counter();
+                let run = match self.opcode(pc).is_some_and(|opcode| opcode & 0xF000 == 0x2000) {
+                    true => Run::StepOver { depth: self.interpreter.stack().len(), to: pc + 2 },
+                    false => Run::StepIn,
+                };
+                self.resume(run);
+                Ok(Value::Null)
+            }
+            "stepIn" => {
+                self.resume(Run::StepIn);
+                Ok(Value::Null)
+            }
+            "stepOut" => {
+                let run = match self.interpreter.stack().len() {
+                    0 => Run::StepIn,
+                    depth => Run::StepOut { depth },
+                };
+                self.resume(run);
+                Ok(Value::Null)
+            }
+            "pause" => Ok(Value::Null),
+            "evaluate" => {
+                let expression = args["expression"].as_str().unwrap_or_default();
+                self.debugger
+                    .execute(&mut self.interpreter, expression)
+                    .map(|result| json!({ "result": result, "variablesReference": 0 }))
+            }
+            "readMemory" => Ok(self.read_memory(args)),
+            "disassemble" => Ok(self.disassemble(args)),
+            "disconnect" | "terminate" => Ok(Value::Null),
+            _ => Err(format!("{command} is not supported")),
+        };
+        let success = result.is_ok();
+        let mut response = json!({
+            "type": "response",
+            "request_seq": request["seq"],
+            "command": command,
+            "success": success,
+        });
+        match result {
+            Ok(body) => response["body"] = body,
+            Err(message) => response["message"] = Value::from(message),
+        }
+        self.send(response)?;
+
+        match command {
+            // Breakpoints are only asked for once the symbols from launch are loaded
+            "launch" if success => self.event("initialized", Value::Null)?,
+            "configurationDone" => {}
+            "pause" if self.running.is_some() => self.stop("pause")?,
+            "disconnect" => return Ok(false),
+            "terminate" => {
+                self.event("terminated", Value::Null)?;
+                return Ok(false);
+            }
+            _ => return Ok(true),
+        }
+        if self.launched && self.configured && !self.started {
+            self.started = true;
+            match self.stop_on_entry {
+                true => self.stop("entry")?,
+                false => self.resume(Run::Continue),
+            }
+        }
+        Ok(true)
+    }
+
+    fn launch(&mut self, args: &Value) -> Result<Value, String> {
+        let program = args["program"].as_str().ok_or("launch needs a program")?;
+        let rom = fs::read(program).map_err(|e| format!("could not read {program}: {e}"))?;
+        let mut overrides = Vec::new();
+        if let Some(quirks) = args["quirks"].as_str() {
+            Quirks::preset(quirks).ok_or_else(|| format!("quirks should be one of {}", Quirks::PRESETS.join(", ")))?;
+            overrides.push(("quirks", quirks.to_string()));
+        }
+        if let Some(speed) = args["speed"].as_u64() {
+            overrides.push(("speed", speed.to_string()));
+        }
+        if let Some(timing) = args["timing"].as_str() {
+            Timing::parse(timing).ok_or("timing should be instructions or vip")?;
+            overrides.push(("timing", timing.to_string()));
+        }
+        // Without a map given, one next to the ROM is used
+        let symbols = match args["symbols"].as_str() {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(Path::new(program).with_extension("sym")).filter(|path| path.is_file()),
+        };
+        if let Some(path) = symbols {
+            self.symbols = SymbolMap::open(&path).map_err(|e| format!("could not read symbols {path:?}: {e}"))?;
+        }
+        self.interpreter.load_bytes(&rom);
+        Session::new(SettingsFile::load(), overrides, &mut self.interpreter);
+        self.interpreter.pause();
+        self.stop_on_entry = args["stopOnEntry"].as_bool().unwrap_or(false);
+        self.launched = true;
+        self.resolve_source_breakpoints();
+        Ok(Value::Null)
+    }
+
+    fn set_breakpoints(&mut self, args: &Value) -> Value {
+        let path = PathBuf::from(args["source"]["path"].as_str().unwrap_or_default());
+        let lines: Vec<u64> = match args["breakpoints"].as_array() {
+            Some(breakpoints) => breakpoints.iter().filter_map(|breakpoint| breakpoint["line"].as_u64()).collect(),
+            None => Vec::new(),
+        };
+        let breakpoints: Vec<Value> = lines
+            .iter()
+            .map(|line| match self.symbols.address(&path, *line) {
+                Some((address, line)) => json!({
+                    "verified": true,
+                    "line": line,
+                    "instructionReference": reference(address),
+                }),
+                None => json!({ "verified": false, "message": "No instructions at or after this line" }),
+            })
+            .collect();
+        self.source_lines.insert(normalise(&path), lines);
+        self.resolve_source_breakpoints();
+        json!({ "breakpoints": breakpoints })
+    }
+
+    fn resolve_source_breakpoints(&mut self) {
+        self.source_breakpoints = self
+            .source_lines
+            .iter()
+            .flat_map(|(path, lines)| lines.iter().filter_map(|line| self.symbols.address(path, *line)))
+            .map(|(address, _)| address)
+            .collect();
+    }
+
+    fn set_instruction_breakpoints(&mut self, args: &Value) -> Value {
+        let requested = args["breakpoints"].as_array().cloned().unwrap_or_default();
+        let addresses: Vec<Option<u16>> = requested
+            .iter()
+            .map(|breakpoint| {
+                let address = parse_reference(breakpoint["instructionReference"].as_str()?)?;
+                let address = address + breakpoint["offset"].as_i64().unwrap_or(0);
+                u16::try_from(address).ok().filter(|address| (*address as usize) < self.interpreter.memory().len())
+            })
+            .collect();
+        self.instruction_breakpoints = addresses.iter().flatten().copied().collect();
+        json!({ "breakpoints": addresses.iter().map(|address| address_breakpoint(*address)).collect::<Vec<_>>() })
+    }
+    /// Function names are taken as addresses, since ROMs carry no names
+    fn set_function_breakpoints(&mut self, args: &Value) -> Value {
+        let requested = args["breakpoints"].as_array().cloned().unwrap_or_default();
+        let addresses: Vec<Option<u16>> = requested
+            .iter()
+            .map(|breakpoint| {
+                let address = parse_reference(breakpoint["name"].as_str()?)?;
+                u16::try_from(address).ok().filter(|address| (*address as usize) < self.interpreter.memory().len())
+            })
+            .collect();
+        self.function_breakpoints = addresses.iter().flatten().copied().collect();
+        json!({ "breakpoints": addresses.iter().map(|address| address_breakpoint(*address)).collect::<Vec<_>>() })
+    }
+
+    fn is_breakpoint(&self, address: u16) -> bool {
+        self.source_breakpoints.contains(&address)
+            || self.instruction_breakpoints.contains(&address)
+            || self.function_breakpoints.contains(&address)
+    }
+    /// The current instruction followed by the call of every subroutine being run, read from the
+    /// return addresses on the stack
+    fn stack_trace(&self) -> Value {
+        let pc = self.interpreter.program_counter();
+        let calls = self.interpreter.stack().iter().rev().map(|ret| ret.wrapping_sub(2));
+        let frames: Vec<Value> = Some(pc)
+            .into_iter()
+            .chain(calls)
+            .enumerate()
+            .map(|(id, address)| {
+                let name = match self.opcode(address).and_then(disassemble) {
+                    Some(mnemonic) => format!("{address:03X} {mnemonic}"),
+                    None => format!("{address:03X}"),
+                };
+                let mut frame = json!({
+                    "id": id,
+                    "name": name,
+                    "line": 0,
+                    "column": 0,
+                    "instructionPointerReference": reference(address),
+                });
+                if let Some((file, line)) = self.symbols.source(address) {
+                    frame["source"] = source(file);
+                    frame["line"] = json!(line);
+                    frame["column"] = json!(1);
+                }
+                frame
+            })
+            .collect();
+        json!({ "stackFrames": frames, "totalFrames": frames.len() })
+    }
+
+    fn variables(&self, args: &Value) -> Value {
+        if args["variablesReference"].as_u64() != Some(REGISTERS) {
+            return json!({ "variables": [] });
+        }
+        let interpreter = &self.interpreter;
+        let variable = |name: String, value: String| json!({ "name": name, "value": value, "variablesReference": 0 });
+        let mut variables: Vec<Value> = interpreter
+            .registers()
+            .iter()
+            .enumerate()
+            .map(|(n, value)| variable(format!("V{n:X}"), format!("0x{value:02X}")))
+            .collect();
+        // Addresses can be opened in a memory view
+        for (name, address) in [("I", interpreter.index()), ("PC", interpreter.program_counter())] {
+            let mut register = variable(name.to_string(), format!("0x{address:03X}"));
+            register["memoryReference"] = json!(reference(address));
+            variables.push(register);
+        }
+        variables.push(variable(String::from("SP"), interpreter.stack().len().to_string()));
+        variables.push(variable(String::from("DT"), format!("0x{:02X}", interpreter.delay_timer())));
+        variables.push(variable(String::from("ST"), format!("0x{:02X}", interpreter.sound_timer())));
+        json!({ "variables": variables })
+    }
+
+    fn read_memory(&self, args: &Value) -> Value {
+        let memory = self.interpreter.memory();
+        // Clients can send any number, so nothing is added without saturating
+        let reference = args["memoryReference"].as_str().and_then(parse_reference).unwrap_or(0);
+        let start = reference.saturating_add(args["offset"].as_i64().unwrap_or(0));
+        let count = args["count"].as_i64().unwrap_or(0).max(0);
+        let first = start.clamp(0, memory.len() as i64);
+        let end = start.saturating_add(count).clamp(first, memory.len() as i64);
+        json!({
+            "address": format!("0x{first:03X}"),
+            "data": base64(&memory[first as usize..end as usize]),
+            "unreadableBytes": count - (end - first),
+        })
+    }
+
+    fn disassemble(&self, args: &Value) -> Value {
+        let start = args["memoryReference"].as_str().and_then(parse_reference).unwrap_or(0)
+            + args["offset"].as_i64().unwrap_or(0)
+            + args["instructionOffset"].as_i64().unwrap_or(0) * 2;
+        let count = args["instructionCount"].as_i64().unwrap_or(0).max(0);
+        let instructions: Vec<Value> = (0..count)
+            .map(|n| start + n * 2)
+            .map(|address| {
+                let Some(opcode) = u16::try_from(address).ok().and_then(|address| self.opcode(address)) else {
+                    return json!({ "address": format!("0x{address:03X}"), "instruction": "", "presentationHint": "invalid" });
+                };
+                let mut instruction = json!({
+                    "address": reference(address as u16),
+                    "instructionBytes": format!("{opcode:04X}"),
+                    "instruction": disassemble(opcode).unwrap_or_default(),
+                });
+                if let Some((file, line)) = self.symbols.source(address as u16) {
+                    instruction["location"] = source(file);
+                    instruction["line"] = json!(line);
+                }
+                instruction
+            })
+            .collect();
+        json!({ "instructions": instructions })
+    }
+
+    fn opcode(&self, address: u16) -> Option<u16> {
+        let memory = self.interpreter.memory();
+        let address = address as usize;
+        Some(u16::from_be_bytes([*memory.get(address)?, *memory.get(address + 1)?]))
+    }
+
+    fn resume(&mut self, run: Run) {
+        if self.running.is_none() {
+            self.next_frame = Instant::now();
+        }
+        self.running = Some(run);
+        self.leaving_breakpoint = true;
+        self.interpreter.resume();
+    }
+
+    fn stop(&mut self, reason: &str) -> io::Result<()> {
+        self.running = None;
+        self.interpreter.pause();
+        self.event("stopped", json!({ "reason": reason, "threadId": THREAD_ID, "allThreadsStopped": true }))
+    }
+    /// Runs the rest of a frame, stopping early at breakpoints and the end of steps
+    fn run_frame(&mut self) -> io::Result<()> {
+        let Some(run) = self.running else {
+            return Ok(());
+        };
+        loop {
+            if !self.leaving_breakpoint && self.is_breakpoint(self.interpreter.program_counter()) {
+                return self.stop("breakpoint");
+            }
+            self.leaving_breakpoint = false;
+            let frame_over = self.interpreter.step_instruction();
+            let depth = self.interpreter.stack().len();
+            let stepped = match run {
+                Run::Continue => false,
+                Run::StepIn => true,
+                Run::StepOver { depth: called_from, to } => self.interpreter.program_counter() == to && depth <= called_from,
+                Run::StepOut { depth: called_from } => depth < called_from,
+            };
+            if stepped {
+                return self.stop("step");
+            }
+            if frame_over {
+                return Ok(());
+            }
+        }
+    }
+
+    fn event(&mut self, event: &str, body: Value) -> io::Result<()> {
+        self.send(json!({ "type": "event", "event": event, "body": body }))
+    }
+
+    fn send(&mut self, mut message: Value) -> io::Result<()> {
+        self.seq += 1;
+        message["seq"] = json!(self.seq);
+        let body = message.to_string();
+        write!(self.output, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.output.flush()
+    }
+}
+
+/// Reads the next message, skipping any that are not valid JSON. `None` once the client has gone
+fn read_message(input: &mut impl BufRead) -> Option<Value> {
+    loop {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            if input.read_line(&mut header).ok()? == 0 {
+                return None;
+            }
+            let header = header.trim();
+            if header.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("Content-Length") {
+                    length = value.trim().parse::<usize>().ok();
+                }
+            }
+        }
+        let Some(length) = length else {
+            continue;
+        };
+        let mut body = vec![0; length];
+        input.read_exact(&mut body).ok()?;
+        if let Ok(message) = serde_json::from_slice(&body) {
+            return Some(message);
+        }
+    }
+}
+
+/// Addresses are referred to as `0x` and hex
+fn reference(address: u16) -> String {
+    format!("0x{address:03X}")
+}
+
+fn parse_reference(reference: &str) -> Option<i64> {
+    let reference = reference.trim();
+    let digits = reference.strip_prefix("0x").or_else(|| reference.strip_prefix("0X")).unwrap_or(reference);
+    i64::from_str_radix(digits, 16).ok()
+}
+
+fn address_breakpoint(address: Option<u16>) -> Value {
+    match address {
+        Some(address) => json!({ "verified": true, "instructionReference": reference(address) }),
+        None => json!({ "verified": false, "message": "Expected an address in hex" }),
+    }
+}
+
+fn source(file: &Path) -> Value {
+    let name = file.file_name().map(|name| name.to_string_lossy()).unwrap_or_default();
+    json!({ "name": name, "path": file.display().to_string() })
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut text = String::new();
+    for chunk in bytes.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (n, byte)| bits | (*byte as u32) << (16 - 8 * n));
+        for n in 0..4 {
+            match n <= chunk.len() {
+                true => text.push(ALPHABET[(bits >> (18 - 6 * n)) as usize & 63] as char),
+                false => text.push('='),
+            }
+        }
+    }
+    text
+}
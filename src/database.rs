@@ -0,0 +1,199 @@
+use std::{collections::HashMap, fs, path::PathBuf};
+
+use serde_json::Value;
+
+use crate::chip8::{parse_colour, Palette, MAX_SPEED, MIN_SPEED};
+use crate::host::Keymap;
+use crate::quirks::Quirks;
+use crate::settings::{config_dir, Settings, SettingsFile};
+
+/// Keyboard characters that the database's named game keys are moved onto
+const GAME_KEYS: [(&str, char); 6] = [("up", 'w'), ("left", 'a'), ("down", 's'), ("right", 'd'), ("a", 'e'), ("b", 'q')];
+
+/// What the database knows about a single ROM
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    /// Platform id the ROM was written for, such as `originalChip8` or `superchip`
+    pub platform: Option<String>,
+    pub quirks: Option<Quirks>,
+    /// Instructions per frame
+    pub tickrate: Option<u32>,
+    /// Hex key for each named game key, such as `up` or `a`
+    pub keys: Vec<(String, usize)>,
+    pub palette: Option<Palette>,
+}
+
+impl RomInfo {
+    /// Applies everything the database knows, returning the settings keys that changed
+    pub fn apply(&self, settings: &mut Settings) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+        if let Some(quirks) = self.quirks {
+            settings.quirks = quirks;
+            changed.push("quirks");
+        }
+        if let Some(tickrate) = self.tickrate {
+            settings.speed = tickrate.clamp(MIN_SPEED, MAX_SPEED);
+            changed.push("speed");
+        }
+        if let Some(palette) = self.palette {
+            settings.palette = palette;
+            changed.push("palette");
+        }
+        let keymap = self.game_keymap(settings.keymap);
+        if keymap != settings.keymap {
+            settings.keymap = keymap;
+            changed.push("keymap");
+        }
+        changed
+    }
+    /// Moves the game's keys onto WASD in one go. Keys that were on those characters get the ones the
+    /// game's keys left behind, in order. A hex key with two names stays on the first one's character
+    fn game_keymap(&self, keymap: Keymap) -> Keymap {
+        let current = keymap.0;
+        let mut targets = [None; 16];
+        for (name, c) in GAME_KEYS {
+            let key = self.keys.iter().find(|(key, _)| key == name).map(|(_, key)| *key);
+            if let Some(key) = key.filter(|key| targets[*key].is_none() && current.contains(&c)) {
+                targets[key] = Some(c);
+            }
+        }
+        let taken: Vec<char> = targets.iter().flatten().copied().collect();
+        let mut freed = (0..16).filter(|key| targets[*key].is_some() && !taken.contains(&current[*key])).map(|key| current[key]);
+        let mut keys = current;
+        for (key, c) in keys.iter_mut().enumerate() {
+            *c = match targets[key] {
+                Some(target) => target,
+                None if taken.contains(c) => freed.next().unwrap_or(*c),
+                None => *c,
+            };
+        }
+        Keymap(keys)
+    }
+    /// Fills in the ROM specific fields from an entry of a program's `roms`
+    fn read(&mut self, rom: &Value) {
+        // The first platform listed is the one the ROM is best played on
+        self.platform = rom["platforms"][0].as_str().map(String::from);
+        if let Some(platform) = &self.platform {
+            self.quirks = platform_quirks(platform).map(|mut quirks| {
+                // Some ROMs need a platform with a few quirks changed
+                if let Some(overrides) = rom["quirkyPlatforms"][platform].as_object() {
+                    for (quirk, value) in overrides {
+                        if let Some(value) = value.as_bool() {
+                            set_quirk(&mut quirks, quirk, value);
+                        }
+                    }
+                }
+                quirks
+            });
+        }
+        self.tickrate = rom["tickrate"].as_u64().map(|tickrate| tickrate as u32);
+        if let Some(keys) = rom["keys"].as_object() {
+            self.keys = keys
+                .iter()
+                .filter_map(|(name, key)| Some((name.clone(), key.as_u64().filter(|key| *key < 16)? as usize)))
+                .collect();
+        }
+        // The first colour is the background and the second is lit pixels
+        let colour = |i: usize| rom["colors"]["pixels"][i].as_str().and_then(parse_colour);
+        if let (Some(off), Some(on)) = (colour(0), colour(1)) {
+            self.palette = Some(Palette { on, off });
+        }
+    }
+    /// Title followed by the authors, as shown in the window title
+    pub fn name(&self) -> String {
+        match self.authors.is_empty() {
+            true => self.title.clone(),
+            false => format!("{} by {}", self.title, self.authors.join(", ")),
+        }
+    }
+}
+
+/// ROMs from the community chip-8-database, keyed by the SHA-1 of the ROM
+#[derive(Clone, Debug, Default)]
+pub struct Database {
+    roms: HashMap<String, RomInfo>,
+}
+
+impl Database {
+    /// Opens the file named by the `database` setting, or `programs.json` in the config directory
+    pub fn load(settings: &SettingsFile) -> Option<Self> {
+        let path = settings.get(None, "database").map(PathBuf::from);
+        path.or_else(|| config_dir().map(|dir| dir.join("programs.json"))).and_then(Self::open)
+    }
+    /// Reads a `programs.json`, `None` when it does not exist or is not valid
+    pub fn open(path: PathBuf) -> Option<Self> {
+        let contents = fs::read_to_string(&path).ok()?;
+        match Self::parse(&contents) {
+            Ok(database) => Some(database),
+            Err(e) => {
+                println!("Could not read ROM database {path:?}: {e}");
+                None
+            }
+        }
+    }
+
+    pub fn parse(json: &str) -> Result<Self, serde_json::Error> {
+        let programs: Vec<Value> = serde_json::from_str(json)?;
+        let mut roms = HashMap::new();
+        for program in &programs {
+            let title = program["title"].as_str().unwrap_or_default();
+            let authors: Vec<String> = program["authors"]
+                .as_array()
+                .map(|authors| authors.iter().filter_map(|author| author.as_str().map(String::from)).collect())
+                .unwrap_or_default();
+            let Some(hashes) = program["roms"].as_object() else {
+                continue;
+            };
+            for (hash, rom) in hashes {
+                let mut info = RomInfo {
+                    title: title.to_string(),
+                    authors: authors.clone(),
+                    ..RomInfo::default()
+                };
+                info.read(rom);
+                roms.insert(hash.to_ascii_lowercase(), info);
+            }
+        }
+        Ok(Self { roms })
+    }
+
+    pub fn get(&self, hash: &str) -> Option<&RomInfo> {
+        self.roms.get(&hash.to_ascii_lowercase())
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+/// Closest quirks preset for a chip-8-database platform id
+fn platform_quirks(platform: &str) -> Option<Quirks> {
+    match platform {
+        "originalChip8" | "hybridVIP" | "chip8x" => Some(Quirks::VIP),
+        "modernChip8" => Some(Quirks::MODERN),
+        "chip48" | "superchip1" | "superchip" | "megachip8" => Some(Quirks::SCHIP),
+        "xochip" => Some(Quirks::XOCHIP),
+        _ => None,
+    }
+}
+
+/// Applies a quirk by its chip-8-database name
+fn set_quirk(quirks: &mut Quirks, quirk: &str, value: bool) {
+    match quirk {
+        "shift" => quirks.shift_uses_vy = !value,
+        "memoryLeaveIUnchanged" => quirks.load_store_increments_i = !value,
+        // Incrementing by `x` instead of `x + 1` is close enough to incrementing
+        "memoryIncrementByX" if value => quirks.load_store_increments_i = true,
+        "wrap" => quirks.clip_sprites = !value,
+        "jump" => quirks.jump_uses_vx = value,
+        "vblank" => quirks.display_wait = value,
+        "logic" => quirks.logic_resets_vf = value,
+        _ => {}
+    }
+}
@@ -0,0 +1,342 @@
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufWriter, Write},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use crate::cheat::{Comparison, RamSearch, Target};
+use crate::chip8::Chip8Interpreter;
+use crate::coverage::Coverage;
+use crate::memview::{find, hex_view, parse_bytes, ROW_BYTES};
+use crate::profile::Profiler;
+use crate::trace::{TraceFormat, Tracer};
+
+/// Bytes `mem` shows when no length is given
+const VIEW_BYTES: usize = 0x80;
+/// Rows `sprite` shows when none are given, the most `Dxyn` can draw
+const SPRITE_ROWS: usize = 0xF;
+/// Matches `find` lists before giving up
+const MAX_MATCHES: usize = 32;
+/// Lines in each part of the profile report
+pub const PROFILE_TOP: usize = 10;
+
+pub const HELP: &str = "\
+Numbers are hex
+  mem [ADDR] [LEN]       show memory, continues from the last view
+  sprite [ADDR] [ROWS]   show memory as a sprite, starts at I
+  set ADDR BYTES...      write bytes, only while paused
+  find BYTES...          list addresses where the bytes appear
+  dump ADDR LEN FILE     save memory to a file
+  load ADDR FILE         read a file into memory, only while paused
+  regs                   show registers
+  pause, resume          stop and start the program
+  step [FRAMES]          run frames while paused
+  trace FILE [FORMAT]    write text, csv or binary records of what runs
+  trace off              stop writing records
+  profile [on|off]       show where instructions ran, or start or stop counting
+  coverage [on|off]      show how much of the ROM was used, or start or stop recording
+  freeze TARGET VALUE    hold an address or a register like V3 at a value every frame
+  unfreeze [TARGET]      stop holding a target, or every target
+  patch ADDR BYTES...    write bytes now and again after every reset
+  cheats                 list freezes and patches
+  search new             start a RAM search with every address as a candidate
+  search eq|changed|inc|dec
+                         keep candidates unchanged, changed, increased or decreased since the last search
+  search eq VALUE        keep candidates that are now a value
+  search                 list the candidates
+In memory views > marks PC, * marks I and @ both";
+
+/// Commands to inspect and edit a running interpreter
+#[derive(Default)]
+pub struct Debugger {
+    // Where `mem` without an address continues from
+    next: Option<usize>,
+    search: Option<RamSearch>,
+}
+
+impl Debugger {
+    /// Runs a command line, returning what to print
+    pub fn execute(&mut self, interpreter: &mut Chip8Interpreter, line: &str) -> Result<String, String> {
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return Ok(String::new());
+        };
+        let args: Vec<&str> = words.collect();
+        let arg = |n: usize| args.get(n).map(|word| parse_number(word)).transpose();
+        let memory_len = interpreter.memory().len();
+        match command {
+            "help" | "h" | "?" => Ok(HELP.to_string()),
+            "mem" | "m" => {
+                let start = match arg(0)? {
+                    Some(start) => start,
+                    None => self.next.unwrap_or(interpreter.program_counter() as usize),
+                };
+                let len = arg(1)?.unwrap_or(VIEW_BYTES);
+                check_range(start, len, memory_len)?;
+                self.next = Some((start + len) % memory_len);
+                Ok(hex_view(interpreter, start, len, ROW_BYTES))
+            }
+            "sprite" => {
+                let start = arg(0)?.unwrap_or(interpreter.index() as usize);
+                let rows = arg(1)?.unwrap_or(SPRITE_ROWS);
+                check_range(start, rows, memory_len)?;
+                Ok(hex_view(interpreter, start, rows, 1))
+            }
+            "set" | "s" => {
+                let start = arg(0)?.ok_or("set needs an address")?;
+                let bytes = parse_bytes(args.iter().skip(1).copied())?;
+                write(interpreter, start, &bytes)?;
+                Ok(hex_view(interpreter, start, bytes.len(), ROW_BYTES))
+            }
+            "find" | "f" => {
+                let bytes = parse_bytes(args.iter().copied())?;
+                if bytes.is_empty() {
+                    return Err(String::from("find needs bytes to look for"));
+                }
+                let matches = find(interpreter.memory(), &bytes);
+                let mut found: Vec<String> = matches.iter().take(MAX_MATCHES).map(|address| format!("{address:03X}")).collect();
+                if matches.len() > MAX_MATCHES {
+                    found.push(format!("and {} more", matches.len() - MAX_MATCHES));
+                }
+                Ok(match found.is_empty() {
+                    true => String::from("not found"),
+                    false => found.join(" "),
+                })
+            }
+            "dump" => {
+                let (Some(start), Some(len), Some(path)) = (arg(0)?, arg(1)?, args.get(2)) else {
+                    return Err(String::from("dump needs an address, a length and a file"));
+                };
+                check_range(start, len, memory_len)?;
+                fs::write(path, &interpreter.memory()[start..start + len])
+                    .map_err(|e| format!("could not write {path}: {e}"))?;
+                Ok(format!("saved {len:X} bytes from {start:03X} to {path}"))
+            }
+            "load" => {
+                let (Some(start), Some(path)) = (arg(0)?, args.get(1)) else {
+                    return Err(String::from("load needs an address and a file"));
+                };
+                let bytes = fs::read(path).map_err(|e| format!("could not read {path}: {e}"))?;
+                write(interpreter, start, &bytes)?;
+                Ok(format!("loaded {:X} bytes from {path} to {start:03X}", bytes.len()))
+            }
+            "regs" | "r" => {
+                let registers: Vec<String> = interpreter
+                    .registers()
+                    .iter()
+                    .enumerate()
+                    .map(|(n, value)| format!("V{n:X}={value:02X}"))
+                    .collect();
+                Ok(format!(
+                    "{}\nI={:03X} PC={:03X}",
+                    registers.join(" "),
+                    interpreter.index(),
+                    interpreter.program_counter(),
+                ))
+            }
+            "pause" => {
+                interpreter.pause();
+                Ok(String::from("paused"))
+            }
+            "resume" | "continue" | "c" => {
+                interpreter.resume();
+                Ok(String::from("resumed"))
+            }
+            "step" => {
+                if !interpreter.is_paused() {
+                    return Err(String::from("pause before stepping"));
+                }
+                for _ in 0..arg(0)?.unwrap_or(1) {
+                    interpreter.step_frame();
+                }
+                Ok(format!("PC={:03X}", interpreter.program_counter()))
+            }
+            "trace" => match args.as_slice() {
+                ["off"] => {
+                    interpreter.set_tracer(None);
+                    Ok(String::from("stopped tracing"))
+                }
+                [path, format @ ..] => {
+                    let format = match format.first() {
+                        Some(name) => TraceFormat::parse(name).ok_or("expected one of text, csv, binary")?,
+                        None => TraceFormat::Text,
+                    };
+                    let file = File::create(path).map_err(|e| format!("could not create {path}: {e}"))?;
+                    interpreter.set_tracer(Some(Tracer::new(Box::new(BufWriter::new(file)), format)));
+                    Ok(format!("tracing to {path}"))
+                }
+                [] => Err(String::from("trace needs a file, or off")),
+            },
+            "profile" => match args.as_slice() {
+                ["on"] => {
+                    interpreter.set_profiler(Some(Profiler::new()));
+                    Ok(String::from("profiling"))
+                }
+                ["off"] => {
+                    interpreter.set_profiler(None);
+                    Ok(String::from("stopped profiling"))
+                }
+                [] => match interpreter.profiler() {
+                    Some(profiler) => Ok(profiler.report(PROFILE_TOP)),
+                    None => Err(String::from("not profiling, start with profile on")),
+                },
+                _ => Err(String::from("expected profile, profile on or profile off")),
+            },
+            "coverage" => match args.as_slice() {
+                ["on"] => {
+                    interpreter.set_coverage(Some(Coverage::new()));
+                    Ok(String::from("recording coverage"))
+                }
+                ["off"] => {
+                    interpreter.set_coverage(None);
+                    Ok(String::from("stopped recording coverage"))
+                }
+                [] => match interpreter.coverage() {
+                    Some(coverage) => Ok(coverage.summary(interpreter.rom().map_or(0, <[u8]>::len))),
+                    None => Err(String::from("not recording coverage, start with coverage on")),
+                },
+                _ => Err(String::from("expected coverage, coverage on or coverage off")),
+            },
+            "freeze" => {
+                let [target, value] = args.as_slice() else {
+                    return Err(String::from("freeze needs an address or register and a value"));
+                };
+                let target = Target::parse(target)?;
+                let value = u8::try_from(parse_number(value)?).map_err(|_| format!("{value} is not a byte"))?;
+                interpreter.freeze(target, value);
+                Ok(format!("froze {target} at {value:02X}"))
+            }
+            "unfreeze" => match args.as_slice() {
+                [] => {
+                    let targets: Vec<Target> = interpreter.cheats().freezes().iter().map(|freeze| freeze.target).collect();
+                    for target in &targets {
+                        interpreter.unfreeze(*target);
+                    }
+                    Ok(format!("unfroze {} targets", targets.len()))
+                }
+                [target] => {
+                    let target = Target::parse(target)?;
+                    match interpreter.unfreeze(target) {
+                        true => Ok(format!("unfroze {target}")),
+                        false => Err(format!("{target} is not frozen")),
+                    }
+                }
+                _ => Err(String::from("expected unfreeze or unfreeze TARGET")),
+            },
+            "patch" => {
+                let start = arg(0)?.ok_or("patch needs an address")?;
+                let bytes = parse_bytes(args.iter().skip(1).copied())?;
+                if bytes.is_empty() {
+                    return Err(String::from("patch needs bytes to write"));
+                }
+                check_range(start, bytes.len(), memory_len)?;
+                interpreter.patch(start as u16, &bytes);
+                Ok(hex_view(interpreter, start, bytes.len(), ROW_BYTES))
+            }
+            "cheats" => {
+                let lines = interpreter.cheats().lines();
+                Ok(match lines.is_empty() {
+                    true => String::from("no cheats"),
+                    false => lines.join("\n"),
+                })
+            }
+            "search" => {
+                let comparison = match args.as_slice() {
+                    ["new"] => {
+                        self.search = Some(RamSearch::new(interpreter.memory()));
+                        return Ok(format!("searching {memory_len} addresses"));
+                    }
+                    [] => None,
+                    [name, value @ ..] => {
+                        let value = match value {
+                            [] => None,
+                            [value] => Some(u8::try_from(parse_number(value)?).map_err(|_| format!("{value} is not a byte"))?),
+                            _ => return Err(String::from("search takes at most one value")),
+                        };
+                        Some(Comparison::parse(name, value).ok_or("expected search new, eq [VALUE], changed, inc or dec")?)
+                    }
+                };
+                let search = self.search.as_mut().ok_or("no search, start one with search new")?;
+                if let Some(comparison) = comparison {
+                    search.narrow(interpreter.memory(), comparison);
+                }
+                let candidates = search.candidates();
+                let mut found: Vec<String> = candidates
+                    .iter()
+                    .take(MAX_MATCHES)
+                    .map(|address| format!("{address:03X}={:02X}", search.previous(*address)))
+                    .collect();
+                if candidates.len() > MAX_MATCHES {
+                    found.push(format!("and {} more", candidates.len() - MAX_MATCHES));
+                }
+                Ok(format!("{} candidates\n{}", candidates.len(), found.join(" ")))
+            }
+            _ => Err(format!("unknown command {command}, try help")),
+        }
+    }
+}
+
+/// Hex with or without a `0x` prefix
+fn parse_number(word: &str) -> Result<usize, String> {
+    usize::from_str_radix(word.trim_start_matches("0x"), 16).map_err(|_| format!("{word} is not a hex number"))
+}
+
+fn check_range(start: usize, len: usize, memory_len: usize) -> Result<(), String> {
+    match start.checked_add(len) {
+        Some(end) if end <= memory_len => Ok(()),
+        _ => Err(format!("{start:03X} plus {len:X} bytes is past the end of memory")),
+    }
+}
+
+/// Edits are only allowed while paused so they don't race the program
+fn write(interpreter: &mut Chip8Interpreter, start: usize, bytes: &[u8]) -> Result<(), String> {
+    if !interpreter.is_paused() {
+        return Err(String::from("pause before editing memory"));
+    }
+    check_range(start, bytes.len(), interpreter.memory().len())?;
+    interpreter.memory_mut()[start..start + bytes.len()].copy_from_slice(bytes);
+    Ok(())
+}
+
+/// Prints the output of a command and the prompt for the next
+pub fn print_result(result: Result<String, String>) {
+    match result {
+        Ok(output) if output.is_empty() => {}
+        Ok(output) => println!("{}", output.trim_end()),
+        Err(e) => println!("error: {e}"),
+    }
+    print!("(chip8) ");
+    let _ = io::stdout().flush();
+}
+
+/// Debugger commands read from stdin while a frontend runs, executed between frames
+pub struct Console {
+    debugger: Debugger,
+    lines: Receiver<String>,
+}
+
+impl Console {
+    /// Starts reading lines from stdin in the background
+    pub fn spawn() -> Self {
+        let (sender, lines) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lock().lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        print_result(Ok(String::from("Debugger console, type help for commands")));
+        Self { debugger: Debugger::default(), lines }
+    }
+    /// Executes the commands that arrived since the last frame
+    pub fn poll(&mut self, interpreter: &mut Chip8Interpreter) {
+        while let Ok(line) = self.lines.try_recv() {
+            print_result(self.debugger.execute(interpreter, &line));
+        }
+    }
+}
@@ -0,0 +1,91 @@
+use alloc::{format, string::String};
+
+/// Mnemonic for an instruction in the style of Cowgod's reference, `None` if it is not one
+pub fn disassemble(opcode: u16) -> Option<String> {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let byte = opcode & 0x00FF;
+    let address = opcode & 0x0FFF;
+    let nimble = opcode & 0x000F;
+    Some(match (opcode >> 12, x, y, nimble) {
+        (0x0, 0x0, 0xE, 0x0) => String::from("CLS"),
+        (0x0, 0x0, 0xE, 0xE) => String::from("RET"),
+        (0x0, ..) => format!("SYS {address:03X}"),
+        (0x1, ..) => format!("JP {address:03X}"),
+        (0x2, ..) => format!("CALL {address:03X}"),
+        (0x3, ..) => format!("SE V{x:X}, {byte:02X}"),
+        (0x4, ..) => format!("SNE V{x:X}, {byte:02X}"),
+        (0x5, _, _, 0x0) => format!("SE V{x:X}, V{y:X}"),
+        (0x6, ..) => format!("LD V{x:X}, {byte:02X}"),
+        (0x7, ..) => format!("ADD V{x:X}, {byte:02X}"),
+        (0x8, _, _, 0x0) => format!("LD V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x1) => format!("OR V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x2) => format!("AND V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x3) => format!("XOR V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x4) => format!("ADD V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x5) => format!("SUB V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x6) => format!("SHR V{x:X}, V{y:X}"),
+        (0x8, _, _, 0x7) => format!("SUBN V{x:X}, V{y:X}"),
+        (0x8, _, _, 0xE) => format!("SHL V{x:X}, V{y:X}"),
+        (0x9, _, _, 0x0) => format!("SNE V{x:X}, V{y:X}"),
+        (0xA, ..) => format!("LD I, {address:03X}"),
+        (0xB, ..) => format!("JP V0, {address:03X}"),
+        (0xC, ..) => format!("RND V{x:X}, {byte:02X}"),
+        (0xD, ..) => format!("DRW V{x:X}, V{y:X}, {nimble:X}"),
+        (0xE, _, 0x9, 0xE) => format!("SKP V{x:X}"),
+        (0xE, _, 0xA, 0x1) => format!("SKNP V{x:X}"),
+        (0xF, _, 0x0, 0x7) => format!("LD V{x:X}, DT"),
+        (0xF, _, 0x0, 0xA) => format!("LD V{x:X}, K"),
+        (0xF, _, 0x1, 0x5) => format!("LD DT, V{x:X}"),
+        (0xF, _, 0x1, 0x8) => format!("LD ST, V{x:X}"),
+        (0xF, _, 0x1, 0xE) => format!("ADD I, V{x:X}"),
+        (0xF, _, 0x2, 0x9) => format!("LD F, V{x:X}"),
+        (0xF, _, 0x3, 0x3) => format!("LD B, V{x:X}"),
+        (0xF, _, 0x5, 0x5) => format!("LD [I], V{x:X}"),
+        (0xF, _, 0x6, 0x5) => format!("LD V{x:X}, [I]"),
+        _ => return None,
+    })
+}
+
+/// Pattern an instruction matches, such as `Dxyn` or `8xy4`, `None` if it is not one
+pub fn pattern(opcode: u16) -> Option<&'static str> {
+    let (x, y, nimble) = ((opcode >> 8) & 0xF, (opcode >> 4) & 0xF, opcode & 0xF);
+    Some(match (opcode >> 12, x, y, nimble) {
+        (0x0, 0x0, 0xE, 0x0) => "00E0",
+        (0x0, 0x0, 0xE, 0xE) => "00EE",
+        (0x0, ..) => "0nnn",
+        (0x1, ..) => "1nnn",
+        (0x2, ..) => "2nnn",
+        (0x3, ..) => "3xkk",
+        (0x4, ..) => "4xkk",
+        (0x5, _, _, 0x0) => "5xy0",
+        (0x6, ..) => "6xkk",
+        (0x7, ..) => "7xkk",
+        (0x8, _, _, 0x0) => "8xy0",
+        (0x8, _, _, 0x1) => "8xy1",
+        (0x8, _, _, 0x2) => "8xy2",
+        (0x8, _, _, 0x3) => "8xy3",
+        (0x8, _, _, 0x4) => "8xy4",
+        (0x8, _, _, 0x5) => "8xy5",
+        (0x8, _, _, 0x6) => "8xy6",
+        (0x8, _, _, 0x7) => "8xy7",
+        (0x8, _, _, 0xE) => "8xyE",
+        (0x9, _, _, 0x0) => "9xy0",
+        (0xA, ..) => "Annn",
+        (0xB, ..) => "Bnnn",
+        (0xC, ..) => "Cxkk",
+        (0xD, ..) => "Dxyn",
+        (0xE, _, 0x9, 0xE) => "Ex9E",
+        (0xE, _, 0xA, 0x1) => "ExA1",
+        (0xF, _, 0x0, 0x7) => "Fx07",
+        (0xF, _, 0x0, 0xA) => "Fx0A",
+        (0xF, _, 0x1, 0x5) => "Fx15",
+        (0xF, _, 0x1, 0x8) => "Fx18",
+        (0xF, _, 0x1, 0xE) => "Fx1E",
+        (0xF, _, 0x2, 0x9) => "Fx29",
+        (0xF, _, 0x3, 0x3) => "Fx33",
+        (0xF, _, 0x5, 0x5) => "Fx55",
+        (0xF, _, 0x6, 0x5) => "Fx65",
+        _ => return None,
+    })
+}
@@ -0,0 +1,89 @@
+use core::fmt;
+
+use crate::chip8::{Chip8Interpreter, KeyEvent, ADDRESS_MASK};
+use crate::font::FONT_SET;
+use crate::quirks::Quirks;
+use crate::timing::Timing;
+
+/// Most frames an input runs for, kept low so more inputs can be tried
+const MAX_FRAMES: u8 = 16;
+/// Key event cycles are given in steps of this many instructions, so they can reach later frames
+const KEY_CYCLE_STEP: u64 = 4;
+
+/// Something the interpreter should never do, whatever the ROM
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// PC has bits set above the 12 that address memory
+    PcOutOfRange(u16),
+    /// Memory is no longer the 4 KiB every address wraps within
+    MemoryResized(usize),
+    /// A byte of the font was changed without the quirks allowing it
+    FontChanged(u16),
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::PcOutOfRange(pc) => write!(f, "PC {pc:04X} is past the end of memory"),
+            Violation::MemoryResized(len) => write!(f, "memory is {len} bytes"),
+            Violation::FontChanged(address) => write!(f, "font changed at {address:03X}"),
+        }
+    }
+}
+
+/// Runs arbitrary bytes as settings, key events and a ROM, checking the interpreter after every instruction.
+/// The first byte picks the quirks preset with its low two bits and VIP timing with the third, the second
+/// how many frames to run, the third how many key events follow. Each event is two bytes, when it happens
+/// and the key with the top bit set for a press. The rest is the ROM
+pub fn run(data: &[u8]) -> Result<(), Violation> {
+    let [settings, frames, events, rest @ ..] = data else {
+        return Ok(());
+    };
+    let events = (*events as usize).min(rest.len() / 2);
+    let (keys, rom) = rest.split_at(events * 2);
+
+    let mut interpreter = Chip8Interpreter::new();
+    let preset = Quirks::PRESETS[*settings as usize % Quirks::PRESETS.len()];
+    interpreter.set_quirks(Quirks::preset(preset).unwrap_or_default());
+    if settings & 0b100 != 0 {
+        interpreter.set_timing(Timing::Vip);
+    }
+    interpreter.set_seed(0);
+    interpreter.load_bytes(rom);
+    for key in keys.chunks_exact(2) {
+        interpreter.queue_key_event(KeyEvent {
+            cycle: key[0] as u64 * KEY_CYCLE_STEP,
+            key: key[1] & 0xF,
+            pressed: key[1] & 0x80 != 0,
+        });
+    }
+
+    for _ in 0..frames % MAX_FRAMES + 1 {
+        loop {
+            let frame_done = interpreter.step_instruction();
+            check(&interpreter)?;
+            if frame_done {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Invariants that hold between any two instructions
+pub fn check(interpreter: &Chip8Interpreter) -> Result<(), Violation> {
+    let pc = interpreter.program_counter();
+    if pc & !ADDRESS_MASK != 0 {
+        return Err(Violation::PcOutOfRange(pc));
+    }
+    if interpreter.memory().len() != ADDRESS_MASK as usize + 1 {
+        return Err(Violation::MemoryResized(interpreter.memory().len()));
+    }
+    if !interpreter.quirks().writes_font {
+        let font = &interpreter.memory()[..FONT_SET.len()];
+        if let Some(address) = font.iter().zip(FONT_SET).position(|(byte, expected)| *byte != expected) {
+            return Err(Violation::FontChanged(address as u16));
+        }
+    }
+    Ok(())
+}
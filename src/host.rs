@@ -0,0 +1,330 @@
+use alloc::{format, vec, vec::Vec};
+
+use crate::chip8::{Chip8Interpreter, FRAME_RATE, HEIGHT, WIDTH};
+use crate::osd::Osd;
+
+/// Shows finished frames
+pub trait Display {
+    /// Called once per frame with the 64x32 RGBA frame and the overlay to draw over it,
+    /// returning false stops the machine
+    fn present(&mut self, frame: &[u8], osd: &Osd) -> bool;
+}
+
+/// Source of keypad state and emulator commands
+pub trait Input {
+    /// Next event since the last poll, called until it returns `None` at the start of every frame
+    fn poll(&mut self) -> Option<InputEvent>;
+}
+
+/// Plays the CHIP-8 tone
+pub trait Audio {
+    /// Called once per frame with whether the sound timer is running
+    fn set_tone(&mut self, on: bool);
+}
+
+/// Paces frames
+pub trait Clock {
+    /// Called at the end of every frame, returns once the next frame should start
+    fn wait_frame(&mut self);
+    /// Seconds since the clock started, used to measure the real frame rate when available
+    fn seconds(&self) -> Option<f64> {
+        None
+    }
+}
+
+pub enum InputEvent {
+    /// Hex keypad key from 0 to F was pressed or released
+    Key(usize, bool),
+    Command(Command),
+    /// Host gained or lost focus, losing it pauses until focus is back
+    Focus(bool),
+    /// Replaces the running ROM
+    Load(Vec<u8>),
+}
+
+/// Emulator controls, see the interpreter methods of the same name
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Command {
+    SpeedDown,
+    SpeedUp,
+    CycleDisplayMode,
+    TogglePause,
+    Reset,
+    FrameAdvance,
+    /// Lasts until sent again with false
+    FastForward(bool),
+    ToggleSlowMotion,
+    /// Shows or hides the performance readout
+    ToggleReadout,
+    Quit,
+}
+
+/// Keyboard characters for each hex keypad key, from 0 to F
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Keymap(pub [char; 16]);
+
+impl Keymap {
+    /// Left side of a qwerty keyboard read row by row as 0 to F
+    pub const LINEAR: Keymap = Keymap([
+        '1', '2', '3', '4',
+        'q', 'w', 'e', 'r',
+        'a', 's', 'd', 'f',
+        'z', 'x', 'c', 'v',
+    ]);
+    /// Same keys laid out like the COSMAC VIP keypad, 1 2 3 C / 4 5 6 D / 7 8 9 E / A 0 B F
+    pub const COSMAC: Keymap = Keymap([
+        'x', '1', '2', '3',
+        'q', 'w', 'e', 'a',
+        's', 'd', 'z', 'c',
+        '4', 'r', 'f', 'v',
+    ]);
+    /// Names accepted by `parse`
+    pub const PRESETS: [&'static str; 2] = ["linear", "cosmac"];
+    /// Characters the frontends use for their own controls, which keypad keys cannot take
+    pub const HOTKEYS: [char; 7] = [' ', '-', '=', 'p', 'n', 'm', 'i'];
+
+    /// Takes a preset name or 16 characters for keys 0 to F, none of them one of the `HOTKEYS`
+    pub fn parse(value: &str) -> Option<Keymap> {
+        match value {
+            "linear" => Some(Keymap::LINEAR),
+            "cosmac" => Some(Keymap::COSMAC),
+            _ => {
+                let mut keys = [' '; 16];
+                let mut chars = value.chars().map(|c| c.to_ascii_lowercase());
+                for key in keys.iter_mut() {
+                    *key = chars.next().filter(|c| !Keymap::HOTKEYS.contains(c))?;
+                }
+                chars.next().is_none().then_some(Keymap(keys))
+            }
+        }
+    }
+    /// Keypad key for a keyboard character
+    pub fn key(&self, c: char) -> Option<usize> {
+        let c = c.to_ascii_lowercase();
+        self.0.iter().position(|k| *k == c)
+    }
+}
+
+/// Preset name, or the 16 characters
+impl core::fmt::Display for Keymap {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match *self {
+            Keymap::LINEAR => write!(f, "linear"),
+            Keymap::COSMAC => write!(f, "cosmac"),
+            Keymap(keys) => keys.iter().try_for_each(|key| write!(f, "{key}")),
+        }
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Keymap::LINEAR
+    }
+}
+
+/// Runs frames of an interpreter against a host's display, input, audio and clock
+pub struct Machine<D, I, A, C> {
+    interpreter: Chip8Interpreter,
+    display: D,
+    input: I,
+    audio: A,
+    clock: C,
+    frame: Vec<u8>,
+    osd: Osd,
+    // Set when focus was lost, so focus only resumes what it paused itself
+    focus_paused: bool,
+}
+
+impl<D: Display, I: Input, A: Audio, C: Clock> Machine<D, I, A, C> {
+    pub fn new(interpreter: Chip8Interpreter, display: D, input: I, audio: A, clock: C) -> Self {
+        Self {
+            interpreter,
+            display,
+            input,
+            audio,
+            clock,
+            frame: vec![0; WIDTH * HEIGHT * 4],
+            osd: Osd::default(),
+            focus_paused: false,
+        }
+    }
+
+    pub fn interpreter(&self) -> &Chip8Interpreter {
+        &self.interpreter
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut Chip8Interpreter {
+        &mut self.interpreter
+    }
+
+    pub fn display_mut(&mut self) -> &mut D {
+        &mut self.display
+    }
+    /// Both at once, for hosts whose display changes the interpreter
+    pub fn interpreter_and_display_mut(&mut self) -> (&mut Chip8Interpreter, &mut D) {
+        (&mut self.interpreter, &mut self.display)
+    }
+
+    pub fn input_mut(&mut self) -> &mut I {
+        &mut self.input
+    }
+
+    pub fn audio_mut(&mut self) -> &mut A {
+        &mut self.audio
+    }
+    /// Toasts and the readout drawn over every frame
+    pub fn osd_mut(&mut self) -> &mut Osd {
+        &mut self.osd
+    }
+
+    pub fn osd(&self) -> &Osd {
+        &self.osd
+    }
+    /// Last frame given to the display
+    pub fn frame(&self) -> &[u8] {
+        &self.frame
+    }
+    /// Applies a single input event, returns false when asked to quit
+    pub fn handle(&mut self, event: InputEvent) -> bool {
+        let interpreter = &mut self.interpreter;
+        match event {
+            InputEvent::Key(key, state) => interpreter.queue_key(key, state),
+            InputEvent::Focus(false) if !interpreter.is_paused() => {
+                interpreter.pause();
+                self.focus_paused = true;
+            }
+            InputEvent::Focus(true) if self.focus_paused => {
+                interpreter.resume();
+                self.focus_paused = false;
+            }
+            InputEvent::Focus(_) => {}
+            InputEvent::Load(rom) => {
+                interpreter.load_bytes(&rom);
+                self.osd.toast("ROM loaded");
+            }
+            InputEvent::Command(command) => match command {
+                Command::SpeedDown | Command::SpeedUp => {
+                    let speed = interpreter.speed();
+                    interpreter.set_speed(if command == Command::SpeedUp { speed + 1 } else { speed - 1 });
+                    self.osd.toast(format!("Speed: {} per second", interpreter.speed() * FRAME_RATE));
+                }
+                Command::CycleDisplayMode => {
+                    interpreter.cycle_display_mode();
+                    self.osd.toast(format!("Display: {}", interpreter.display_mode()));
+                }
+                Command::TogglePause => {
+                    interpreter.toggle_pause();
+                    self.focus_paused = false;
+                    self.osd.toast(if interpreter.is_paused() { "Paused" } else { "Resumed" });
+                }
+                Command::Reset => {
+                    interpreter.reset();
+                    self.osd.toast("Reset");
+                }
+                Command::FrameAdvance => {
+                    interpreter.pause();
+                    interpreter.step_frame();
+                }
+                Command::FastForward(state) => interpreter.set_fast_forward(state),
+                Command::ToggleSlowMotion => {
+                    interpreter.set_slow_motion(!interpreter.is_slow_motion());
+                    self.osd.toast(if interpreter.is_slow_motion() { "Slow motion on" } else { "Slow motion off" });
+                }
+                Command::ToggleReadout => self.osd.set_readout(!self.osd.readout()),
+                Command::Quit => return false,
+            },
+        }
+        true
+    }
+    /// Polls input, runs the interpreter, then presents, sounds and waits for the next frame.
+    /// Returns false once the host asked to stop
+    pub fn run_frame(&mut self) -> bool {
+        while let Some(event) = self.input.poll() {
+            if !self.handle(event) {
+                return false;
+            }
+        }
+        self.interpreter.run_frame();
+        self.interpreter.draw_pixels(&mut self.frame);
+        self.osd.update(&self.interpreter, self.clock.seconds());
+        if !self.display.present(&self.frame, &self.osd) {
+            return false;
+        }
+        self.audio.set_tone(self.interpreter.should_beep());
+        self.clock.wait_frame();
+        true
+    }
+    /// Runs frames until the host asks to stop
+    pub fn run(&mut self) {
+        while self.run_frame() {}
+    }
+}
+
+/// Headless hosts can use `()` for any part they do not need
+impl Display for () {
+    fn present(&mut self, _: &[u8], _: &Osd) -> bool {
+        true
+    }
+}
+
+impl Input for () {
+    fn poll(&mut self) -> Option<InputEvent> {
+        None
+    }
+}
+
+impl Audio for () {
+    fn set_tone(&mut self, _: bool) {}
+}
+
+impl Clock for () {
+    fn wait_frame(&mut self) {}
+}
+
+/// Hosts without an audio device stay silent
+impl<A: Audio> Audio for Option<A> {
+    fn set_tone(&mut self, on: bool) {
+        if let Some(audio) = self {
+            audio.set_tone(on);
+        }
+    }
+}
+
+/// Events queued by hosts that receive them through callbacks
+impl Input for alloc::collections::VecDeque<InputEvent> {
+    fn poll(&mut self) -> Option<InputEvent> {
+        self.pop_front()
+    }
+}
+
+/// Sleeps the rest of a fixed frame period
+#[cfg(feature = "std")]
+pub struct SleepClock {
+    period: std::time::Duration,
+    last_frame: std::time::Instant,
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl SleepClock {
+    pub fn new(period: std::time::Duration) -> Self {
+        Self {
+            period,
+            last_frame: std::time::Instant::now(),
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clock for SleepClock {
+    fn wait_frame(&mut self) {
+        let frame_time = self.last_frame.elapsed();
+        std::thread::sleep(self.period.saturating_sub(frame_time));
+        self.last_frame = std::time::Instant::now();
+    }
+
+    fn seconds(&self) -> Option<f64> {
+        Some(self.start.elapsed().as_secs_f64())
+    }
+}
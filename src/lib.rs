@@ -1,3 +1,47 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+extern crate alloc;
+
+/// `println!` when std is available, otherwise the arguments are only type checked
+macro_rules! log {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "std")]
+        std::println!($($arg)*);
+        #[cfg(not(feature = "std"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+pub mod cheat;
 pub mod chip8;
+pub mod coverage;
+#[cfg(feature = "dap")]
+pub mod dap;
+#[cfg(feature = "database")]
+pub mod database;
+pub mod disasm;
 pub mod font;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+pub mod host;
+pub mod memview;
+pub mod observer;
+pub mod osd;
+pub mod profile;
+pub mod quirks;
+pub mod text;
+pub mod timing;
+pub mod trace;
+#[cfg(feature = "std")]
+pub mod compat;
+#[cfg(feature = "std")]
+pub mod debugger;
+#[cfg(feature = "std")]
+pub mod menu;
+#[cfg(feature = "std")]
+pub mod settings;
+#[cfg(feature = "audio")]
+pub mod audio;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "window")]
 pub mod window;
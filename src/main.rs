@@ -1,61 +1,700 @@
-use std::{env, path::{Path, PathBuf}};
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, BufWriter},
+    ops::RangeInclusive,
+    panic, thread,
+    path::{Path, PathBuf},
+    process::ExitCode,
+};
 
-use chip8::{chip8::Chip8Interpreter, window::{Chip8Window, GameEvents, MINUS_POSITION, PLUS_POSITION}};
+use chip8::{
+    cheat::CheatFile,
+    chip8::{Chip8Interpreter, DisplayMode, KeyEvent, Palette, HEIGHT, WIDTH},
+    compat::{self, check_all, find_roms, screenshot_name, WORKER_THREAD},
+    coverage::{Coverage, HEATMAP_COLUMNS},
+    debugger::{print_result, Debugger, PROFILE_TOP},
+    disasm::disassemble,
+    host::{Keymap, Machine},
+    menu::add_recent,
+    profile::Profiler,
+    quirks::Quirks,
+    timing::Timing,
+    settings::{config_dir, Session, SettingsFile},
+    trace::{first_divergence, OpcodePattern, TraceFormat, TraceReader, TraceRecord, Tracer, Trigger},
+};
+#[cfg(feature = "database")]
+use chip8::database::Database;
+#[cfg(feature = "tui")]
+use chip8::tui::{self, Glyphs};
+#[cfg(feature = "window")]
+use chip8::debugger::Console;
+#[cfg(feature = "window")]
+use chip8::window::{Chip8Window, ScalingMode, WindowOptions};
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
-fn main() {
-    let window = Chip8Window::new();
-    let mut interpreter = Chip8Interpreter::new();
+/// A fairly simple CHIP-8 interpreter
+#[derive(Parser)]
+#[command(name = "chip8", version, args_conflicts_with_subcommands = true)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Commands>,
+    /// Running without a subcommand is the same as `run`
+    #[command(flatten)]
+    run: RunArgs,
+}
+
+#[derive(Subcommand)]
+enum Commands {
+    /// Play a ROM in a window or terminal
+    Run(RunArgs),
+    /// Show details about a ROM
+    Info {
+        rom: PathBuf,
+    },
+    /// Print every instruction of a ROM
+    Disasm {
+        rom: PathBuf,
+    },
+    /// Run a ROM without a display and print the final screen, ignoring the settings file
+    Headless(HeadlessArgs),
+    /// Show where two traces written with --trace first differ
+    TraceDiff(TraceDiffArgs),
+    /// Run every ROM in a directory under each quirks preset and write a report of how they did
+    Compat(CompatArgs),
+    /// Inspect and edit a ROM's memory from a prompt, frames only run when stepped. Ignores the settings file
+    Debug {
+        rom: PathBuf,
+        #[command(flatten)]
+        machine: MachineArgs,
+    },
+    /// Serve the Debug Adapter Protocol on stdin and stdout for an editor to launch ROMs with
+    #[cfg(feature = "dap")]
+    Dap {
+        /// Wait for the editor to connect to this port on localhost instead, 0 picks any free port
+        #[arg(long)]
+        port: Option<u16>,
+    },
+}
+
+/// Options shared by everything that runs a ROM, settings given here are not saved
+#[derive(Args)]
+struct MachineArgs {
+    /// Instructions per second, rounded to a multiple of 60 between 60 and 15000
+    #[arg(long)]
+    speed: Option<u32>,
+    /// Behaviour of ambiguous instructions: modern, vip, schip or xochip
+    #[arg(long, value_parser = parse_quirks)]
+    quirks: Option<Quirks>,
+    /// How long instructions take: instructions, or vip to run at the speed of the COSMAC VIP
+    #[arg(long, value_parser = parse_timing)]
+    timing: Option<Timing>,
+    /// Seed for random numbers, the same seed and inputs always play out the same
+    #[arg(long)]
+    seed: Option<u64>,
+    /// mono, amber, green, lcd or colours for lit and unlit pixels as RRGGBB,RRGGBB
+    #[arg(long, value_parser = parse_palette)]
+    palette: Option<Palette>,
+    /// Write a record for every executed instruction to this file
+    #[arg(long, value_name = "FILE")]
+    trace: Option<PathBuf>,
+    /// text, csv or binary
+    #[arg(long, value_name = "FORMAT", value_parser = parse_trace_format, default_value = "text")]
+    trace_format: TraceFormat,
+    /// Only trace instructions between two hex addresses like 200-2FF, can be repeated
+    #[arg(long, value_name = "START-END", value_parser = parse_range)]
+    trace_range: Vec<RangeInclusive<u16>>,
+    /// Only trace instructions matching a pattern like Dxyn or 8xy4, can be repeated
+    #[arg(long, value_name = "PATTERN", value_parser = parse_opcode_pattern)]
+    trace_opcode: Vec<OpcodePattern>,
+    /// Start tracing at a hex address or at cycle:N
+    #[arg(long, value_name = "TRIGGER", value_parser = parse_trigger)]
+    trace_start: Option<Trigger>,
+    /// Stop tracing at a hex address or at cycle:N, until the start trigger is hit again
+    #[arg(long, value_name = "TRIGGER", value_parser = parse_trigger)]
+    trace_stop: Option<Trigger>,
+    /// Count where instructions run and write PREFIX.txt, PREFIX.folded and PREFIX-loops.csv when done
+    #[arg(long, value_name = "PREFIX")]
+    profile: Option<PathBuf>,
+    /// Record which bytes are run, drawn, read and written and write PREFIX.json and PREFIX.png when done
+    #[arg(long, value_name = "PREFIX")]
+    coverage: Option<PathBuf>,
+    /// Replay key events, with the speed, quirks and timing they were recorded at, from a file written by --record-keys
+    #[arg(long, value_name = "FILE")]
+    keys: Option<PathBuf>,
+    /// Write the speed, quirks, timing and every key event with when it happened to this file when done
+    #[arg(long, value_name = "FILE")]
+    record_keys: Option<PathBuf>,
+    /// Freezes and patches for each ROM by SHA-1, instead of cheats.txt in the config directory
+    #[arg(long, value_name = "FILE")]
+    cheats: Option<PathBuf>,
+    /// Log internal state every instruction, 2 also logs the display and memory around PC and I
+    #[arg(short, long, value_name = "LEVEL", default_value_t = 0)]
+    debug: u8,
+}
+
+#[derive(Args)]
+struct RunArgs {
+    /// ROM to play, can also be dropped onto the window
+    rom: Option<PathBuf>,
+    #[command(flatten)]
+    machine: MachineArgs,
+    #[arg(long, value_enum, default_value_t = Frontend::Window)]
+    frontend: Frontend,
+    /// Use braille in the terminal for a smaller picture
+    #[arg(long)]
+    braille: bool,
+    /// Read debugger commands from stdin while the window is open
+    #[cfg(feature = "window")]
+    #[arg(long)]
+    console: bool,
+    /// Keys for 0 to F: linear, cosmac or 16 characters
+    #[arg(long, value_parser = parse_keymap)]
+    keymap: Option<Keymap>,
+    /// Let pixels fade out over this many frames to reduce flicker
+    #[arg(short = 'p', long, value_name = "FRAMES")]
+    persistence: Option<u8>,
+    /// Show everything drawn since the last frame to reduce flicker
+    #[arg(short = 'v', long)]
+    vblank: bool,
+    #[cfg(feature = "window")]
+    #[command(flatten)]
+    window: WindowArgs,
+}
+
+/// Window options override what was remembered from the last session
+#[cfg(feature = "window")]
+#[derive(Args)]
+struct WindowArgs {
+    #[arg(short, long)]
+    fullscreen: bool,
+    /// Fixed whole number scale of the window
+    #[arg(short, long)]
+    scale: Option<u32>,
+    /// pixel, fit or stretch
+    #[arg(short = 'm', long, value_parser = parse_scaling)]
+    scaling: Option<ScalingMode>,
+    /// Colour around the frame as RRGGBB
+    #[arg(short, long, value_parser = parse_border)]
+    border: Option<[u8; 3]>,
+}
+
+#[derive(Args)]
+struct HeadlessArgs {
+    rom: PathBuf,
+    #[command(flatten)]
+    machine: MachineArgs,
+    /// Frames to run for, there are 60 every second
+    #[arg(long, default_value_t = 600)]
+    frames: u32,
+}
+
+#[derive(Args)]
+struct TraceDiffArgs {
+    a: PathBuf,
+    b: PathBuf,
+    /// Instructions to show before the first difference
+    #[arg(long, default_value_t = 5)]
+    context: usize,
+    /// Leave out the timers, which depend on how fast each emulator runs
+    #[arg(long)]
+    ignore_timers: bool,
+}
+
+#[derive(Args)]
+struct CompatArgs {
+    dir: PathBuf,
+    /// Frames to run each ROM for, there are 60 every second
+    #[arg(long, default_value_t = 600)]
+    frames: u32,
+    /// Directory to write the report and screenshots to
+    #[arg(long, default_value = "compat")]
+    out: PathBuf,
+    #[arg(long, value_enum, default_value_t = ReportFormat::Markdown)]
+    format: ReportFormat,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Frontend {
+    Window,
+    Tui,
+}
+
+fn parse_quirks(value: &str) -> Result<Quirks, String> {
+    Quirks::preset(value).ok_or_else(|| format!("expected one of {}", Quirks::PRESETS.join(", ")))
+}
+
+fn parse_timing(value: &str) -> Result<Timing, String> {
+    Timing::parse(value).ok_or_else(|| String::from("expected instructions or vip"))
+}
+
+fn parse_palette(value: &str) -> Result<Palette, String> {
+    Palette::parse(value).ok_or_else(|| format!("expected one of {} or RRGGBB,RRGGBB", Palette::PRESETS.join(", ")))
+}
+
+fn parse_keymap(value: &str) -> Result<Keymap, String> {
+    Keymap::parse(value).ok_or_else(|| {
+        let hotkeys: String = Keymap::HOTKEYS.iter().collect();
+        format!("expected one of {} or 16 characters other than {hotkeys:?}", Keymap::PRESETS.join(", "))
+    })
+}
+
+fn parse_trace_format(value: &str) -> Result<TraceFormat, String> {
+    TraceFormat::parse(value).ok_or_else(|| String::from("expected one of text, csv, binary"))
+}
+
+fn parse_range(value: &str) -> Result<RangeInclusive<u16>, String> {
+    let address = |value: &str| u16::from_str_radix(value.trim().trim_start_matches("0x"), 16).ok();
+    value
+        .split_once('-')
+        .and_then(|(start, end)| Some(address(start)?..=address(end)?))
+        .ok_or_else(|| String::from("expected two hex addresses like 200-2FF"))
+}
+
+fn parse_opcode_pattern(value: &str) -> Result<OpcodePattern, String> {
+    OpcodePattern::parse(value).ok_or_else(|| String::from("expected 4 characters like Dxyn, 00E0 or 8xy4"))
+}
 
-    // CLI args of: chip8 <PATH> -d <LEVEL>
-    let args: Vec<String> = env::args().collect();
-    // Process debug first to immediately begin logging
-    if let Some(debug) = args.iter().position(|arg| arg == "-d") {
-        let level = args.get(debug+1)
-            .and_then(|val| val.parse().ok())
-            .unwrap_or(0);
-        interpreter.set_debug(level)
+fn parse_trigger(value: &str) -> Result<Trigger, String> {
+    Trigger::parse(value).ok_or_else(|| String::from("expected a hex address or cycle:N"))
+}
+
+#[cfg(feature = "window")]
+fn parse_scaling(value: &str) -> Result<ScalingMode, String> {
+    ScalingMode::parse(value).ok_or_else(|| String::from("expected one of pixel, fit, stretch"))
+}
+
+#[cfg(feature = "window")]
+fn parse_border(value: &str) -> Result<[u8; 3], String> {
+    chip8::chip8::parse_colour(value).ok_or_else(|| String::from("expected a colour as RRGGBB"))
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    let result = match cli.command.unwrap_or(Commands::Run(cli.run)) {
+        Commands::Run(args) => run(args),
+        Commands::Info { rom } => info(rom),
+        Commands::Disasm { rom } => disasm(rom),
+        Commands::Headless(args) => headless(args),
+        Commands::TraceDiff(args) => trace_diff(args),
+        Commands::Compat(args) => compat(args),
+        Commands::Debug { rom, machine } => debug(rom, machine),
+        #[cfg(feature = "dap")]
+        Commands::Dap { port } => match port {
+            Some(port) => chip8::dap::serve_tcp(port),
+            None => chip8::dap::serve_stdio(),
+        }
+        .map_err(|e| format!("debug adapter failed: {e}")),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}");
+            ExitCode::FAILURE
+        }
     }
-    // Now when loading rom from args, there will be logs 
-    if let Some(file) = args.get(1) {
-        if Path::new(file).is_file() {
-            if let Err(e) = interpreter.load_rom(PathBuf::from(file)) {
-                println!("Could not load ROM: {e}");
+}
+
+impl MachineArgs {
+    fn interpreter(&self) -> Result<Chip8Interpreter, String> {
+        let mut interpreter = Chip8Interpreter::new();
+        // Process debug first to immediately begin logging
+        if self.debug > 0 {
+            interpreter.set_debug(self.debug);
+        }
+        if let Some(seed) = self.seed {
+            interpreter.set_seed(seed);
+        }
+        if let Some(path) = &self.trace {
+            let file = File::create(path).map_err(|e| format!("could not create {path:?}: {e}"))?;
+            let mut tracer = Tracer::new(Box::new(BufWriter::new(file)), self.trace_format);
+            for range in &self.trace_range {
+                tracer = tracer.with_range(range.clone());
             }
+            for pattern in &self.trace_opcode {
+                tracer = tracer.with_opcode(*pattern);
+            }
+            if let Some(trigger) = self.trace_start {
+                tracer = tracer.with_start(trigger);
+            }
+            if let Some(trigger) = self.trace_stop {
+                tracer = tracer.with_stop(trigger);
+            }
+            interpreter.set_tracer(Some(tracer));
+        }
+        if self.profile.is_some() {
+            interpreter.set_profiler(Some(Profiler::new()));
         }
+        if self.coverage.is_some() {
+            interpreter.set_coverage(Some(Coverage::new()));
+        }
+        interpreter.record_keys(self.record_keys.is_some());
+        // Only a file given on the command line has to exist
+        let cheats = match &self.cheats {
+            Some(path) => Some((path.clone(), fs::read_to_string(path).map_err(|e| format!("could not read cheats {path:?}: {e}"))?)),
+            None => config_dir()
+                .map(|dir| dir.join("cheats.txt"))
+                .and_then(|path| fs::read_to_string(&path).ok().map(|text| (path, text))),
+        };
+        if let Some((path, text)) = cheats {
+            let file = CheatFile::parse(&text).map_err(|e| format!("{path:?} {e}"))?;
+            interpreter.set_cheat_file(Some(file));
+        }
+        Ok(interpreter)
     }
-
-    
-    let mut iteration_speed = 1;
-    window.run(move |event, pixels| {
-        match event {
-            GameEvents::DroppedFile(path_buf) => {
-                if let Err(e) = interpreter.load_rom(path_buf) {
-                    println!("Could not load ROM: {e}");
-                }
-            },
-            GameEvents::KeyInput(position, state) => {
-                if position < 16 {
-                    interpreter.update_key(position, state);
-                } else {
-                    if (position == MINUS_POSITION) && iteration_speed > 0 {
-                        iteration_speed -= 1;
+    /// Loads a ROM and queues the key events to replay on it, returning the settings they were
+    /// recorded with
+    fn load(&self, interpreter: &mut Chip8Interpreter, rom: PathBuf) -> Result<Vec<(&'static str, String)>, String> {
+        interpreter
+            .load_rom(rom.clone())
+            .map_err(|e| format!("could not load ROM {rom:?}: {e}"))?;
+        let mut recorded = Vec::new();
+        if let Some(path) = &self.keys {
+            let text = fs::read_to_string(path).map_err(|e| format!("could not read keys {path:?}: {e}"))?;
+            for (n, line) in text.lines().enumerate().filter(|(_, line)| !line.trim().is_empty()) {
+                match line.split_once(' ') {
+                    Some(("speed", value)) => recorded.push(("speed", value.trim().to_string())),
+                    Some(("quirks", value)) => recorded.push(("quirks", value.trim().to_string())),
+                    Some(("timing", value)) => recorded.push(("timing", value.trim().to_string())),
+                    _ => {
+                        let event = KeyEvent::parse(line)
+                            .ok_or_else(|| format!("{path:?} line {}: expected CYCLE KEY down|up", n + 1))?;
+                        interpreter.queue_key_event(event);
                     }
-
-                    if (position == PLUS_POSITION) && iteration_speed < 250 {
-                        iteration_speed += 1;
-                    }
-                    assert!(iteration_speed > 0 && iteration_speed < 250);
                 }
-                
-            },
-            GameEvents::Redraw => {
-                for _ in 0..iteration_speed {
-                    interpreter.execute_cycle();
-                }
-                interpreter.draw_pixels(pixels);
-                return interpreter.should_beep();
-            },
+            }
+        }
+        Ok(recorded)
+    }
+    /// Writes out the profile, coverage and keys when they were asked for
+    fn finish(&self, interpreter: &Chip8Interpreter) -> Result<(), String> {
+        if let (Some(prefix), Some(profiler)) = (&self.profile, interpreter.profiler()) {
+            write_profile(prefix, profiler)?;
+        }
+        if let (Some(prefix), Some(coverage)) = (&self.coverage, interpreter.coverage()) {
+            write_coverage(prefix, coverage, interpreter.rom().map_or(0, <[u8]>::len))?;
+        }
+        if let Some(path) = &self.record_keys {
+            let settings = format!(
+                "speed {}\nquirks {}\ntiming {}\n",
+                interpreter.speed() * chip8::chip8::FRAME_RATE,
+                interpreter.quirks(),
+                interpreter.timing()
+            );
+            let events = interpreter.recorded_keys().iter().map(|event| format!("{event}\n"));
+            let lines: String = std::iter::once(settings).chain(events).collect();
+            fs::write(path, lines).map_err(|e| format!("could not write keys {path:?}: {e}"))?;
+        }
+        Ok(())
+    }
+    /// Settings that take priority over the settings file, on top of the ones keys were recorded with
+    fn overrides(&self, recorded: Vec<(&'static str, String)>) -> Vec<(&'static str, String)> {
+        let mut overrides = recorded;
+        if let Some(speed) = self.speed {
+            overrides.push(("speed", speed.to_string()));
+        }
+        if let Some(name) = self.quirks.and_then(|quirks| quirks.name()) {
+            overrides.push(("quirks", name.to_string()));
+        }
+        if let Some(palette) = self.palette {
+            overrides.push(("palette", palette.to_string()));
+        }
+        if let Some(timing) = self.timing {
+            overrides.push(("timing", timing.to_string()));
+        }
+        overrides
+    }
+}
+
+/// Report, folded stacks for flamegraphs and hot loops, each next to the prefix
+fn write_profile(prefix: &Path, profiler: &Profiler) -> Result<(), String> {
+    let path = |suffix| with_suffix(prefix, suffix);
+    for (path, contents) in [
+        (path(".txt"), profiler.report(PROFILE_TOP)),
+        (path(".folded"), profiler.folded()),
+        (path("-loops.csv"), profiler.hot_loops_csv()),
+    ] {
+        fs::write(&path, contents).map_err(|e| format!("could not write profile {path:?}: {e}"))?;
+    }
+    println!("Profile written to {}.txt", prefix.display());
+    Ok(())
+}
+
+/// Ranges of bytes used in each way as JSON and a heatmap of memory
+fn write_coverage(prefix: &Path, coverage: &Coverage, rom_len: usize) -> Result<(), String> {
+    const SCALE: usize = 8;
+    let path = with_suffix(prefix, ".json");
+    fs::write(&path, coverage.json(rom_len)).map_err(|e| format!("could not write coverage {path:?}: {e}"))?;
+
+    let (width, height) = (HEATMAP_COLUMNS * SCALE, coverage.heatmap_height(SCALE));
+    write_png(&with_suffix(prefix, ".png"), width, height, &coverage.heatmap(rom_len, SCALE))?;
+    println!("Coverage written to {}.json", prefix.display());
+    Ok(())
+}
+
+fn write_png(path: &Path, width: usize, height: usize, rgb: &[u8]) -> Result<(), String> {
+    let file = File::create(path).map_err(|e| format!("could not create {path:?}: {e}"))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(rgb))
+        .map_err(|e| format!("could not write {path:?}: {e}"))
+}
+
+/// `prefix` with `suffix` appended to the file name
+fn with_suffix(prefix: &Path, suffix: &str) -> PathBuf {
+    let mut path = prefix.as_os_str().to_owned();
+    path.push(suffix);
+    PathBuf::from(path)
+}
+
+fn run(args: RunArgs) -> Result<(), String> {
+    let mut interpreter = args.machine.interpreter()?;
+    // Now when loading rom from args, there will be logs
+    let mut recorded = Vec::new();
+    if let Some(rom) = args.rom {
+        recorded = args.machine.load(&mut interpreter, rom.clone())?;
+        if let Err(e) = add_recent(rom) {
+            println!("Could not save recent files: {e}");
+        }
+    }
+    let mut overrides = args.machine.overrides(recorded);
+    if let Some(keymap) = args.keymap {
+        overrides.push(("keymap", keymap.to_string()));
+    }
+    // Flicker reduction, can also be cycled at runtime with tab
+    if let Some(frames) = args.persistence {
+        overrides.push(("display", DisplayMode::Persistence(frames).to_string()));
+    }
+    if args.vblank {
+        overrides.push(("display", DisplayMode::Vblank.to_string()));
+    }
+    let mut session = Session::new(SettingsFile::load(), overrides, &mut interpreter);
+    let machine = args.machine;
+    session.on_exit(move |interpreter| {
+        if let Err(e) = machine.finish(interpreter) {
+            println!("{e}");
         }
-        false
     });
+
+    match args.frontend {
+        #[cfg(feature = "window")]
+        Frontend::Window => {
+            let mut options = WindowOptions::read(session.file());
+            let window = args.window;
+            if window.fullscreen {
+                options.fullscreen = true;
+            }
+            if let Some(scale) = window.scale {
+                options.scale = Some(scale).filter(|scale| *scale > 0);
+            }
+            if let Some(scaling) = window.scaling {
+                options.scale = None;
+                options.scaling = scaling;
+            }
+            if let Some(border) = window.border {
+                options.border = border;
+            }
+            let mut window = Chip8Window::with_options(options);
+            if args.console {
+                window = window.with_console(Console::spawn());
+            }
+            window.run(interpreter, session);
+            Ok(())
+        }
+        #[cfg(feature = "tui")]
+        Frontend::Tui => {
+            let glyphs = match args.braille {
+                true => Glyphs::Braille,
+                false => Glyphs::HalfBlock,
+            };
+            tui::run(interpreter, glyphs, session)
+                .map_err(|e| format!("could not run in terminal: {e}"))
+        }
+        #[allow(unreachable_patterns)]
+        frontend => {
+            drop(session);
+            Err(format!(
+                "the {} frontend was not enabled when building",
+                frontend.to_possible_value().map(|value| value.get_name().to_owned()).unwrap_or_default(),
+            ))
+        }
+    }
+}
+
+fn read_rom(rom: &PathBuf) -> Result<Vec<u8>, String> {
+    fs::read(rom).map_err(|e| format!("could not read ROM {rom:?}: {e}"))
+}
+
+fn info(rom: PathBuf) -> Result<(), String> {
+    let bytes = read_rom(&rom)?;
+    // Programs start at 0x200 and can use the rest of the 4096 bytes of memory
+    const MAX_SIZE: usize = 4096 - 0x200;
+    println!("File:         {}", rom.display());
+    println!("Size:         {} bytes{}", bytes.len(), match bytes.len() > MAX_SIZE {
+        true => format!(", {} bytes past the end of memory are ignored", bytes.len() - MAX_SIZE),
+        false => String::new(),
+    });
+    let hash = sha1_smol::Sha1::from(&bytes).digest().to_string();
+    println!("SHA-1:        {hash}");
+    #[cfg(feature = "database")]
+    if let Some(info) = Database::load(&SettingsFile::load()).as_ref().and_then(|database| database.get(&hash)) {
+        println!("Title:        {}", info.name());
+        if let Some(platform) = &info.platform {
+            println!("Platform:     {platform}");
+        }
+        if let Some(tickrate) = info.tickrate {
+            println!("Speed:        {} instructions per second", tickrate * chip8::chip8::FRAME_RATE);
+        }
+    }
+    let opcodes: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+        .collect();
+    let known = opcodes.iter().filter(|opcode| disassemble(**opcode).is_some()).count();
+    println!("Instructions: {known} of {} words decode as instructions, the rest are likely data", opcodes.len());
+    // Instructions whose behaviour depends on quirks, these hint at which preset a ROM needs
+    let count = |matches: fn(u16) -> bool| opcodes.iter().filter(|opcode| matches(**opcode)).count();
+    println!("Quirk sensitive instructions:");
+    println!("  Shifts (8xy6, 8xyE):        {}", count(|op| op & 0xF00F == 0x8006 || op & 0xF00F == 0x800E));
+    println!("  Logic (8xy1, 8xy2, 8xy3):   {}", count(|op| matches!(op & 0xF00F, 0x8001..=0x8003)));
+    println!("  Load/store (Fx55, Fx65):    {}", count(|op| op & 0xF0FF == 0xF055 || op & 0xF0FF == 0xF065));
+    println!("  Jump with offset (Bnnn):    {}", count(|op| op & 0xF000 == 0xB000));
+    println!("  Draw (Dxyn):                {}", count(|op| op & 0xF000 == 0xD000));
+    println!("  Wait for key (Fx0A):        {}", count(|op| op & 0xF0FF == 0xF00A));
+    Ok(())
+}
+
+fn disasm(rom: PathBuf) -> Result<(), String> {
+    let bytes = read_rom(&rom)?;
+    for (i, pair) in bytes.chunks(2).enumerate() {
+        let address = 0x200 + i * 2;
+        match pair {
+            [high, low] => {
+                let opcode = u16::from_be_bytes([*high, *low]);
+                let mnemonic = disassemble(opcode).unwrap_or_default();
+                println!("{address:03X}: {opcode:04X}  {mnemonic}");
+            }
+            [byte] => println!("{address:03X}: {byte:02X}"),
+            _ => unreachable!(),
+        }
+    }
+    Ok(())
+}
+
+fn headless(args: HeadlessArgs) -> Result<(), String> {
+    let mut interpreter = args.machine.interpreter()?;
+    let recorded = args.machine.load(&mut interpreter, args.rom)?;
+    // Only the command line decides how the ROM runs, so the same arguments always give the same screen
+    Session::new(SettingsFile::default(), args.machine.overrides(recorded), &mut interpreter);
+    let mut machine = Machine::new(interpreter, (), (), (), ());
+    for _ in 0..args.frames {
+        machine.run_frame();
+    }
+    for row in machine.interpreter().vram().chunks(WIDTH).take(HEIGHT) {
+        let line: String = row.iter().map(|pixel| if *pixel { '#' } else { '.' }).collect();
+        println!("{line}");
+    }
+    args.machine.finish(machine.interpreter())
+}
+
+fn trace_diff(args: TraceDiffArgs) -> Result<(), String> {
+    let open = |path: &PathBuf| {
+        File::open(path)
+            .and_then(|file| TraceReader::new(BufReader::new(file)))
+            .map_err(|e| format!("could not read trace {path:?}: {e}"))
+    };
+    let (a, b) = (open(&args.a)?, open(&args.b)?);
+    let divergence = first_divergence(a, b, !args.ignore_timers, args.context)
+        .map_err(|e| format!("could not read traces: {e}"))?;
+    let Some(divergence) = divergence else {
+        println!("No differences found");
+        return Ok(());
+    };
+    let line = |record: &TraceRecord| {
+        format!("{record}  {}", disassemble(record.opcode).unwrap_or_default())
+    };
+    println!("First difference at cycle {}: {}", divergence.cycle, divergence.differences.join(", "));
+    let context = divergence.context.len().saturating_sub(args.context);
+    for record in &divergence.context[context..] {
+        println!("     {}", line(record));
+    }
+    println!("  a: {}", line(&divergence.a));
+    println!("  b: {}", line(&divergence.b));
+    // Differing state comes from the last instruction that ran, unless the instruction itself differs
+    if let Some(previous) = divergence.context.last() {
+        println!("Last instruction before it: {:03X} {:04X}  {}", previous.pc, previous.opcode,
+            disassemble(previous.opcode).unwrap_or_default());
+    }
+    Err(String::from("the traces differ"))
+}
+
+fn compat(args: CompatArgs) -> Result<(), String> {
+    const SCALE: usize = 4;
+    const SCREENSHOTS: &str = "screenshots";
+    let roms = find_roms(&args.dir).map_err(|e| format!("could not list {:?}: {e}", args.dir))?;
+    if roms.is_empty() {
+        return Err(format!("no ROMs found in {:?}", args.dir));
+    }
+    println!("Checking {} ROMs under {} presets", roms.len(), Quirks::PRESETS.len());
+    // Panics are expected from broken ROMs and reported, not printed, anything else still is
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(move |info| {
+        if thread::current().name() != Some(WORKER_THREAD) {
+            hook(info);
+        }
+    }));
+    let reports = check_all(&roms, args.frames);
+
+    let screenshots = args.out.join(SCREENSHOTS);
+    fs::create_dir_all(&screenshots).map_err(|e| format!("could not create {screenshots:?}: {e}"))?;
+    for report in &reports {
+        let mut rgb = Vec::with_capacity(WIDTH * HEIGHT * SCALE * SCALE * 3);
+        for row in report.screen.chunks(WIDTH) {
+            for _ in 0..SCALE {
+                for pixel in row {
+                    let colour = if *pixel { Palette::MONO.on } else { Palette::MONO.off };
+                    (0..SCALE).for_each(|_| rgb.extend_from_slice(&colour));
+                }
+            }
+        }
+        write_png(&screenshots.join(screenshot_name(report)), WIDTH * SCALE, HEIGHT * SCALE, &rgb)?;
+    }
+    let (name, contents) = match args.format {
+        ReportFormat::Markdown => ("report.md", compat::markdown(&reports, SCREENSHOTS)),
+        ReportFormat::Html => ("index.html", compat::html(&reports, SCREENSHOTS)),
+    };
+    let path = args.out.join(name);
+    fs::write(&path, contents).map_err(|e| format!("could not write {path:?}: {e}"))?;
+    for report in reports.iter().filter(|report| !report.outcome.is_ok()) {
+        println!("{} ({}): {}", report.rom.display(), report.preset, report.outcome);
+    }
+    println!("Report written to {}", path.display());
+    Ok(())
+}
+
+fn debug(rom: PathBuf, machine: MachineArgs) -> Result<(), String> {
+    let mut interpreter = machine.interpreter()?;
+    let recorded = machine.load(&mut interpreter, rom)?;
+    // Like `headless`, the settings file is left out so sessions can be repeated anywhere
+    Session::new(SettingsFile::default(), machine.overrides(recorded), &mut interpreter);
+    interpreter.pause();
+    let mut debugger = Debugger::default();
+    print_result(Ok(String::from("Type help for commands, quit to exit")));
+    for line in io::stdin().lock().lines() {
+        let line = line.map_err(|e| format!("could not read command: {e}"))?;
+        if matches!(line.trim(), "quit" | "q" | "exit") {
+            break;
+        }
+        print_result(debugger.execute(&mut interpreter, &line));
+    }
+    println!();
+    machine.finish(&interpreter)
 }
@@ -0,0 +1,93 @@
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::Write;
+
+use crate::chip8::Chip8Interpreter;
+
+/// Bytes shown on each line of `hex_view`
+pub const ROW_BYTES: usize = 8;
+
+/// Hex dump of the lines holding `len` bytes from `start` with ASCII and sprite columns, `width` bytes a line.
+/// The byte at PC is marked with `>`, the byte at I with `*` and `@` when they are the same
+pub fn hex_view(interpreter: &Chip8Interpreter, start: usize, len: usize, width: usize) -> String {
+    let memory = interpreter.memory();
+    let width = width.max(1);
+    let (pc, i) = (interpreter.program_counter() as usize, interpreter.index() as usize);
+    let first = (start / width * width).min(memory.len());
+    let end = (start + len.max(1)).div_ceil(width) * width;
+    let end = end.min(memory.len());
+    let mut view = String::new();
+    for (row, bytes) in memory[first..end].chunks(width).enumerate() {
+        let address = first + row * width;
+        let _ = write!(view, "{address:03X} ");
+        for column in 0..width {
+            let at = address + column;
+            let marker = match (at == pc, at == i) {
+                (true, true) => '@',
+                (true, false) => '>',
+                (false, true) => '*',
+                (false, false) => ' ',
+            };
+            match bytes.get(column) {
+                Some(byte) => {
+                    let _ = write!(view, "{marker}{byte:02X}");
+                }
+                None => view.push_str("   "),
+            }
+        }
+        view.push_str("  ");
+        for column in 0..width {
+            view.push(match bytes.get(column) {
+                Some(byte @ 0x20..=0x7E) => *byte as char,
+                Some(_) => '.',
+                None => ' ',
+            });
+        }
+        view.push_str("  |");
+        for byte in bytes {
+            view.push_str(&sprite_row(*byte));
+        }
+        view.push_str("|\n");
+    }
+    view
+}
+
+/// A byte as it would be drawn by `Dxyn`, two pixels to a character
+fn sprite_row(byte: u8) -> String {
+    (0..4)
+        .map(|pair| match (byte >> (6 - pair * 2)) & 0b11 {
+            0b00 => ' ',
+            0b01 => '▐',
+            0b10 => '▌',
+            _ => '█',
+        })
+        .collect()
+}
+
+/// Every address the sequence of bytes starts at
+pub fn find(memory: &[u8], bytes: &[u8]) -> Vec<usize> {
+    if bytes.is_empty() {
+        return Vec::new();
+    }
+    memory
+        .windows(bytes.len())
+        .enumerate()
+        .filter(|(_, window)| *window == bytes)
+        .map(|(address, _)| address)
+        .collect()
+}
+
+/// Bytes written as hex, either separated by spaces or run together like `A21E`
+pub fn parse_bytes<'a>(words: impl IntoIterator<Item = &'a str>) -> Result<Vec<u8>, String> {
+    let mut bytes = Vec::new();
+    for word in words {
+        let digits = word.trim_start_matches("0x");
+        if digits.is_empty() || digits.len() % 2 != 0 {
+            return Err(format!("{word} is not a whole number of hex bytes"));
+        }
+        for pair in digits.as_bytes().chunks(2) {
+            let pair = core::str::from_utf8(pair).map_err(|_| format!("{word} is not hex"))?;
+            bytes.push(u8::from_str_radix(pair, 16).map_err(|_| format!("{word} is not hex"))?);
+        }
+    }
+    Ok(bytes)
+}
@@ -0,0 +1,190 @@
+use std::{env, fs, path::PathBuf};
+
+use crate::settings::config_dir;
+use crate::text::{text_width, Canvas, CHAR_WIDTH, LINE_HEIGHT};
+
+/// File extensions listed by the menu
+const EXTENSIONS: [&str; 3] = ["ch8", "sc8", "xo8"];
+/// Recently played ROMs kept, newest first
+const MAX_RECENT: usize = 10;
+/// Lines the menu is sized to fit, the text scale is picked from this
+const MENU_LINES: usize = 24;
+
+const TEXT: [u8; 4] = [220, 220, 220, 255];
+const DIM: [u8; 4] = [130, 130, 130, 255];
+const HIGHLIGHT: [u8; 4] = [255, 255, 255, 48];
+const ERROR: [u8; 4] = [255, 110, 90, 255];
+
+/// Keys the menu responds to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MenuKey {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Select,
+}
+
+/// Lists recently played ROMs followed by the ROMs in a directory
+pub struct RomMenu {
+    directory: PathBuf,
+    recent: Vec<PathBuf>,
+    files: Vec<PathBuf>,
+    selected: usize,
+    error: Option<String>,
+}
+
+impl RomMenu {
+    /// Lists the ROMs in a directory, the current directory is used when there is none
+    pub fn new(directory: Option<PathBuf>) -> Self {
+        let directory = directory.or_else(|| env::current_dir().ok()).unwrap_or_default();
+        let mut menu = Self {
+            directory,
+            recent: load_recent(),
+            files: Vec::new(),
+            selected: 0,
+            error: None,
+        };
+        menu.refresh();
+        menu
+    }
+    /// Reads the directory and recent files again
+    pub fn refresh(&mut self) {
+        self.recent = load_recent();
+        self.files = fs::read_dir(&self.directory)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .filter(|path| path.is_file() && is_rom(path))
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.files.sort_by_key(|path| path.file_name().map(|name| name.to_string_lossy().to_lowercase()));
+        self.selected = self.selected.min(self.len().saturating_sub(1));
+    }
+    /// Shown at the bottom until the next ROM is chosen
+    pub fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+    /// Moves the selection, returns the chosen ROM on `Select`
+    pub fn handle(&mut self, key: MenuKey) -> Option<PathBuf> {
+        const PAGE: usize = MENU_LINES / 2;
+        let last = self.len().saturating_sub(1);
+        self.selected = match key {
+            MenuKey::Up => self.selected.checked_sub(1).unwrap_or(last),
+            MenuKey::Down if self.selected >= last => 0,
+            MenuKey::Down => self.selected + 1,
+            MenuKey::PageUp => self.selected.saturating_sub(PAGE),
+            MenuKey::PageDown => (self.selected + PAGE).min(last),
+            MenuKey::Home => 0,
+            MenuKey::End => last,
+            MenuKey::Select => {
+                let path = self.entry(self.selected)?.clone();
+                self.error = None;
+                return Some(path);
+            }
+        };
+        None
+    }
+    /// Draws the whole menu over the canvas
+    pub fn draw(&self, canvas: &mut Canvas, background: [u8; 3]) {
+        let [r, g, b] = background;
+        let (width, height) = (canvas.width(), canvas.height());
+        canvas.fill_rect(0, 0, width, height, [r, g, b, 255]);
+        let scale = (height / (LINE_HEIGHT * MENU_LINES)).max(1);
+        let line = LINE_HEIGHT * scale;
+        let margin = CHAR_WIDTH * scale;
+        let columns = (width.saturating_sub(margin * 2) / (CHAR_WIDTH * scale)).max(1);
+        let fit = |text: String| -> String {
+            match text.chars().count() > columns {
+                true => text.chars().take(columns.saturating_sub(1)).chain(Some('~')).collect(),
+                false => text,
+            }
+        };
+
+        let mut y = margin;
+        canvas.draw_text(margin, y, "CHIP-8", TEXT, scale);
+        y += line;
+        canvas.draw_text(margin, y, &fit(self.directory.display().to_string()), DIM, scale);
+        y += line * 2;
+
+        // Title, directory, footer and the gaps between them take 6 lines
+        let rows = (height.saturating_sub(y + margin) / line).saturating_sub(2).max(1);
+        let first = (self.selected + 1).saturating_sub(rows);
+        if self.len() == 0 {
+            canvas.draw_text(margin, y, &fit(String::from("No ROMs found, drop one onto the window")), DIM, scale);
+        }
+        for index in first..self.len().min(first + rows) {
+            let Some(path) = self.entry(index) else {
+                break;
+            };
+            let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+            let recent = index < self.recent.len();
+            if index == self.selected {
+                canvas.fill_rect(margin / 2, y - scale, width - margin, line, HIGHLIGHT);
+            }
+            let marker = if recent { "* " } else { "  " };
+            canvas.draw_text(margin, y, &fit(format!("{marker}{name}")), if recent { TEXT } else { DIM }, scale);
+            y += line;
+        }
+
+        let footer = height.saturating_sub(margin + line);
+        match &self.error {
+            Some(error) => canvas.draw_text(margin, footer, &fit(error.clone()), ERROR, scale),
+            None => {
+                let help = "Enter: play  Esc: back  * recent";
+                let help = if text_width(help, scale) <= width { help } else { "Enter: play" };
+                canvas.draw_text(margin, footer, help, DIM, scale)
+            }
+        };
+    }
+
+    fn len(&self) -> usize {
+        self.recent.len() + self.files.len()
+    }
+
+    fn entry(&self, index: usize) -> Option<&PathBuf> {
+        match index.checked_sub(self.recent.len()) {
+            Some(index) => self.files.get(index),
+            None => self.recent.get(index),
+        }
+    }
+}
+
+pub(crate) fn is_rom(path: &std::path::Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str()))
+}
+
+fn recent_path() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("recent.txt"))
+}
+
+/// Recently played ROMs that still exist, newest first
+pub fn load_recent() -> Vec<PathBuf> {
+    let Some(contents) = recent_path().and_then(|path| fs::read_to_string(path).ok()) else {
+        return Vec::new();
+    };
+    contents.lines().map(PathBuf::from).filter(|path| path.is_file()).collect()
+}
+
+/// Moves a ROM to the top of the recent files
+pub fn add_recent(rom: PathBuf) -> std::io::Result<()> {
+    let Some(path) = recent_path() else {
+        return Ok(());
+    };
+    let rom = rom.canonicalize().unwrap_or(rom);
+    let mut recent = load_recent();
+    recent.retain(|path| *path != rom);
+    recent.insert(0, rom);
+    recent.truncate(MAX_RECENT);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let lines: Vec<String> = recent.iter().map(|path| path.display().to_string()).collect();
+    fs::write(path, lines.join("\n") + "\n")
+}
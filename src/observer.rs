@@ -0,0 +1,116 @@
+use alloc::{boxed::Box, rc::Rc, vec::Vec};
+use core::cell::RefCell;
+
+use crate::chip8::Chip8Interpreter;
+
+/// Tooling attached to an interpreter with `set_observer`, called as the machine runs. Every method
+/// does nothing unless implemented, and gets the machine as it is when the event happens
+#[allow(unused_variables)]
+pub trait Observer {
+    /// An instruction was read at `pc`, PC has not moved past it yet
+    fn fetch(&mut self, machine: &Chip8Interpreter, pc: u16, opcode: u16) {}
+    /// About to run the instruction fetched at `pc`, PC already points past it
+    fn before_execute(&mut self, machine: &Chip8Interpreter, pc: u16, opcode: u16) {}
+    /// The instruction fetched at `pc` has run
+    fn after_execute(&mut self, machine: &Chip8Interpreter, pc: u16, opcode: u16) {}
+    /// `Fx65` loaded a byte into a register
+    fn memory_read(&mut self, machine: &Chip8Interpreter, address: u16, value: u8) {}
+    /// `Fx33` or `Fx55` stored a byte, stores the font is protected from are not reported
+    fn memory_write(&mut self, machine: &Chip8Interpreter, address: u16, value: u8) {}
+    /// `Dxyn` drew `rows` bytes from `address` at a position on the screen
+    fn sprite_draw(&mut self, machine: &Chip8Interpreter, x: u8, y: u8, address: u16, rows: u8, collision: bool) {}
+    /// `Dxyn` read a row of its sprite, rows clipped off the bottom of the screen are never read
+    fn sprite_row(&mut self, machine: &Chip8Interpreter, address: u16, value: u8) {}
+    /// The tone started or stopped, following `should_beep`
+    fn sound(&mut self, machine: &Chip8Interpreter, on: bool) {}
+    /// `Fx0A` started waiting for a key to put in Vx
+    fn key_wait(&mut self, machine: &Chip8Interpreter, x: u8) {}
+    /// `Fx0A` got its key
+    fn key_wait_done(&mut self, machine: &Chip8Interpreter, key: u8) {}
+}
+
+/// Lets a tool be installed while its owner keeps a handle to read results from
+impl<T: Observer> Observer for Rc<RefCell<T>> {
+    fn fetch(&mut self, machine: &Chip8Interpreter, pc: u16, opcode: u16) {
+        self.borrow_mut().fetch(machine, pc, opcode);
+    }
+
+    fn before_execute(&mut self, machine: &Chip8Interpreter, pc: u16, opcode: u16) {
+        self.borrow_mut().before_execute(machine, pc, opcode);
+    }
+
+    fn after_execute(&mut self, machine: &Chip8Interpreter, pc: u16, opcode: u16) {
+        self.borrow_mut().after_execute(machine, pc, opcode);
+    }
+
+    fn memory_read(&mut self, machine: &Chip8Interpreter, address: u16, value: u8) {
+        self.borrow_mut().memory_read(machine, address, value);
+    }
+
+    fn memory_write(&mut self, machine: &Chip8Interpreter, address: u16, value: u8) {
+        self.borrow_mut().memory_write(machine, address, value);
+    }
+
+    fn sprite_draw(&mut self, machine: &Chip8Interpreter, x: u8, y: u8, address: u16, rows: u8, collision: bool) {
+        self.borrow_mut().sprite_draw(machine, x, y, address, rows, collision);
+    }
+
+    fn sprite_row(&mut self, machine: &Chip8Interpreter, address: u16, value: u8) {
+        self.borrow_mut().sprite_row(machine, address, value);
+    }
+
+    fn sound(&mut self, machine: &Chip8Interpreter, on: bool) {
+        self.borrow_mut().sound(machine, on);
+    }
+
+    fn key_wait(&mut self, machine: &Chip8Interpreter, x: u8) {
+        self.borrow_mut().key_wait(machine, x);
+    }
+
+    fn key_wait_done(&mut self, machine: &Chip8Interpreter, key: u8) {
+        self.borrow_mut().key_wait_done(machine, key);
+    }
+}
+
+/// Several tools at once, called in order
+impl Observer for Vec<Box<dyn Observer>> {
+    fn fetch(&mut self, machine: &Chip8Interpreter, pc: u16, opcode: u16) {
+        self.iter_mut().for_each(|observer| observer.fetch(machine, pc, opcode));
+    }
+
+    fn before_execute(&mut self, machine: &Chip8Interpreter, pc: u16, opcode: u16) {
+        self.iter_mut().for_each(|observer| observer.before_execute(machine, pc, opcode));
+    }
+
+    fn after_execute(&mut self, machine: &Chip8Interpreter, pc: u16, opcode: u16) {
+        self.iter_mut().for_each(|observer| observer.after_execute(machine, pc, opcode));
+    }
+
+    fn memory_read(&mut self, machine: &Chip8Interpreter, address: u16, value: u8) {
+        self.iter_mut().for_each(|observer| observer.memory_read(machine, address, value));
+    }
+
+    fn memory_write(&mut self, machine: &Chip8Interpreter, address: u16, value: u8) {
+        self.iter_mut().for_each(|observer| observer.memory_write(machine, address, value));
+    }
+
+    fn sprite_draw(&mut self, machine: &Chip8Interpreter, x: u8, y: u8, address: u16, rows: u8, collision: bool) {
+        self.iter_mut().for_each(|observer| observer.sprite_draw(machine, x, y, address, rows, collision));
+    }
+
+    fn sprite_row(&mut self, machine: &Chip8Interpreter, address: u16, value: u8) {
+        self.iter_mut().for_each(|observer| observer.sprite_row(machine, address, value));
+    }
+
+    fn sound(&mut self, machine: &Chip8Interpreter, on: bool) {
+        self.iter_mut().for_each(|observer| observer.sound(machine, on));
+    }
+
+    fn key_wait(&mut self, machine: &Chip8Interpreter, x: u8) {
+        self.iter_mut().for_each(|observer| observer.key_wait(machine, x));
+    }
+
+    fn key_wait_done(&mut self, machine: &Chip8Interpreter, key: u8) {
+        self.iter_mut().for_each(|observer| observer.key_wait_done(machine, key));
+    }
+}
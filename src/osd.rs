@@ -0,0 +1,115 @@
+use alloc::{collections::VecDeque, format, string::String};
+
+use crate::chip8::{Chip8Interpreter, FRAME_RATE};
+use crate::text::{text_width, Canvas, LINE_HEIGHT};
+
+/// Frames a toast stays on screen
+const TOAST_FRAMES: u32 = 2 * FRAME_RATE;
+/// Older toasts are dropped once there are more than this
+const MAX_TOASTS: usize = 4;
+/// Seconds between updates of the readout's rates
+const SAMPLE_PERIOD: f64 = 1.0;
+
+const TEXT: [u8; 4] = [255, 255, 255, 255];
+const BACKGROUND: [u8; 4] = [0, 0, 0, 160];
+
+/// Status messages and performance counters drawn over the game
+#[derive(Default)]
+pub struct Osd {
+    // Message and the frames it has left
+    toasts: VecDeque<(String, u32)>,
+    readout: bool,
+    // Frame, instruction count and time of the last sample, rates are measured from it
+    sample: Option<(u64, u64, f64)>,
+    frames: u64,
+    instructions_per_second: u32,
+    frames_per_second: f32,
+    speed: u32,
+    status: &'static str,
+}
+
+impl Osd {
+    /// Shows a message for a couple of seconds
+    pub fn toast(&mut self, message: impl Into<String>) {
+        if self.toasts.len() >= MAX_TOASTS {
+            self.toasts.pop_front();
+        }
+        self.toasts.push_back((message.into(), TOAST_FRAMES));
+    }
+    /// Messages currently shown, oldest first
+    pub fn toasts(&self) -> impl Iterator<Item = &str> {
+        self.toasts.iter().map(|(message, _)| message.as_str())
+    }
+
+    pub fn set_readout(&mut self, readout: bool) {
+        self.readout = readout;
+    }
+
+    pub fn readout(&self) -> bool {
+        self.readout
+    }
+    /// Instructions and frames per second, speed and run state, `None` while hidden
+    pub fn readout_text(&self) -> Option<String> {
+        self.readout.then(|| {
+            let mut text = format!(
+                "{} IPS {:.0} FPS x{}",
+                self.instructions_per_second, self.frames_per_second, self.speed
+            );
+            if !self.status.is_empty() {
+                text.push(' ');
+                text.push_str(self.status);
+            }
+            text
+        })
+    }
+    /// Called once a frame with the seconds since the host started when it can tell,
+    /// otherwise frames are assumed to be on time
+    pub fn update(&mut self, interpreter: &Chip8Interpreter, seconds: Option<f64>) {
+        for toast in self.toasts.iter_mut() {
+            toast.1 = toast.1.saturating_sub(1);
+        }
+        self.toasts.retain(|(_, frames)| *frames > 0);
+
+        self.frames += 1;
+        let now = seconds.unwrap_or(self.frames as f64 / FRAME_RATE as f64);
+        let instructions = interpreter.instruction_count();
+        match self.sample {
+            Some((frames, count, then)) if now - then >= SAMPLE_PERIOD => {
+                let elapsed = now - then;
+                self.frames_per_second = ((self.frames - frames) as f64 / elapsed) as f32;
+                self.instructions_per_second = (instructions.wrapping_sub(count) as f64 / elapsed) as u32;
+                self.sample = Some((self.frames, instructions, now));
+            }
+            Some(_) => {}
+            None => self.sample = Some((self.frames, instructions, now)),
+        }
+        self.speed = interpreter.speed();
+        self.status = if interpreter.is_paused() {
+            "PAUSED"
+        } else if interpreter.is_fast_forward() {
+            "FAST"
+        } else if interpreter.is_slow_motion() {
+            "SLOW"
+        } else {
+            ""
+        };
+    }
+    /// Draws the readout in the top right corner and toasts in the bottom left
+    pub fn draw(&self, canvas: &mut Canvas, scale: usize) {
+        let line = LINE_HEIGHT * scale;
+        let pad = 2 * scale;
+        let boxed_text = |canvas: &mut Canvas, x: usize, y: usize, text: &str| {
+            canvas.fill_rect(x, y, text_width(text, scale) + pad, line + pad, BACKGROUND);
+            canvas.draw_text(x + pad, y + pad, text, TEXT, scale);
+        };
+        if let Some(text) = self.readout_text() {
+            let x = canvas.width().saturating_sub(text_width(&text, scale) + pad);
+            boxed_text(canvas, x, 0, &text);
+        }
+        let mut y = canvas.height().saturating_sub(self.toasts.len() * (line + pad));
+        for message in self.toasts() {
+            boxed_text(canvas, 0, y, message);
+            y += line + pad;
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use alloc::{collections::BTreeMap, format, string::String, vec, vec::Vec};
+use core::fmt::Write;
+
+use crate::disasm::{disassemble, pattern};
+
+/// Counts where instructions run and which subroutines they run in
+#[derive(Clone, Debug)]
+pub struct Profiler {
+    total: u64,
+    hits: Vec<u64>,
+    // Last opcode run from each address, so reports can name instructions
+    opcodes: Vec<u16>,
+    classes: BTreeMap<&'static str, u64>,
+    // Entry addresses of the subroutines being run, outermost first, and instructions run in them
+    stacks: BTreeMap<Vec<u16>, u64>,
+    path: Vec<u16>,
+    // Backwards jumps from the second address to the first and how often they were taken
+    back_edges: BTreeMap<(u16, u16), u64>,
+    last: Option<(u16, u16)>,
+}
+
+/// Instructions run in a subroutine, `None` is the code that is not in one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Subroutine {
+    pub entry: Option<u16>,
+    /// Including the subroutines it calls
+    pub inclusive: u64,
+    pub exclusive: u64,
+}
+
+/// Code between a backwards jump and where it jumps to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HotLoop {
+    pub start: u16,
+    pub end: u16,
+    pub iterations: u64,
+    /// Instructions run between the start and end, also counting any run outside of the loop
+    pub instructions: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self {
+            total: 0,
+            hits: vec![0; 4096],
+            opcodes: vec![0; 4096],
+            classes: BTreeMap::new(),
+            stacks: BTreeMap::new(),
+            path: Vec::new(),
+            back_edges: BTreeMap::new(),
+            last: None,
+        }
+    }
+    /// Called before every instruction with the return addresses on the stack, subroutines are
+    /// found by reading the `2nnn` each one was called from
+    pub fn record(&mut self, pc: u16, opcode: u16, memory: &[u8], stack: &[u16]) {
+        self.total += 1;
+        if let Some(hits) = self.hits.get_mut(pc as usize) {
+            *hits += 1;
+            self.opcodes[pc as usize] = opcode;
+        }
+        *self.classes.entry(pattern(opcode).unwrap_or("data")).or_default() += 1;
+
+        self.path.clear();
+        for ret in stack {
+            let call = ret.wrapping_sub(2);
+            let entry = match memory.get(call as usize..call as usize + 2) {
+                Some([high, low]) if high >> 4 == 0x2 => u16::from_be_bytes([*high, *low]) & 0x0FFF,
+                // Called code that has since been overwritten is named after where it was called from
+                _ => call,
+            };
+            self.path.push(entry);
+        }
+        match self.stacks.get_mut(self.path.as_slice()) {
+            Some(count) => *count += 1,
+            None => {
+                self.stacks.insert(self.path.clone(), 1);
+            }
+        }
+
+        if let Some((last_pc, last_opcode)) = self.last {
+            if matches!(last_opcode & 0xF000, 0x1000 | 0xB000) && pc <= last_pc {
+                *self.back_edges.entry((pc, last_pc)).or_default() += 1;
+            }
+        }
+        self.last = Some((pc, opcode));
+    }
+
+    pub fn total(&self) -> u64 {
+        self.total
+    }
+    /// Times the instruction at an address was run
+    pub fn hits(&self, address: u16) -> u64 {
+        self.hits.get(address as usize).copied().unwrap_or_default()
+    }
+    /// Instructions run for each pattern like `Dxyn`, with `data` for anything that is not an instruction
+    pub fn classes(&self) -> impl Iterator<Item = (&'static str, u64)> + '_ {
+        self.classes.iter().map(|(class, count)| (*class, *count))
+    }
+    /// Every subroutine that ran, most expensive first
+    pub fn subroutines(&self) -> Vec<Subroutine> {
+        let mut subroutines: BTreeMap<Option<u16>, Subroutine> = BTreeMap::new();
+        for (path, count) in &self.stacks {
+            let mut entries: Vec<Option<u16>> = Some(None).into_iter().chain(path.iter().map(|entry| Some(*entry))).collect();
+            let innermost = *entries.last().unwrap_or(&None);
+            // Recursion only counts once towards inclusive cost
+            entries.sort();
+            entries.dedup();
+            for entry in entries {
+                let subroutine = subroutines.entry(entry).or_insert(Subroutine { entry, inclusive: 0, exclusive: 0 });
+                subroutine.inclusive += count;
+                if entry == innermost {
+                    subroutine.exclusive += count;
+                }
+            }
+        }
+        let mut subroutines: Vec<Subroutine> = subroutines.into_values().collect();
+        subroutines.sort_by_key(|subroutine| core::cmp::Reverse(subroutine.inclusive));
+        subroutines
+    }
+    /// Loops found from backwards jumps, most instructions first
+    pub fn hot_loops(&self) -> Vec<HotLoop> {
+        let mut loops: Vec<HotLoop> = self
+            .back_edges
+            .iter()
+            .map(|((start, end), iterations)| HotLoop {
+                start: *start,
+                end: *end,
+                iterations: *iterations,
+                instructions: self.hits[*start as usize..=*end as usize].iter().sum(),
+            })
+            .collect();
+        loops.sort_by_key(|hot_loop| core::cmp::Reverse(hot_loop.instructions));
+        loops
+    }
+    /// A line for each call stack, like `main;sub_2A4;sub_310 1234`, as read by flamegraph tools
+    pub fn folded(&self) -> String {
+        let mut folded = String::new();
+        for (path, count) in &self.stacks {
+            folded.push_str("main");
+            for entry in path {
+                let _ = write!(folded, ";{}", name(Some(*entry)));
+            }
+            let _ = writeln!(folded, " {count}");
+        }
+        folded
+    }
+
+    pub fn hot_loops_csv(&self) -> String {
+        let mut csv = String::from("start,end,iterations,instructions,share\n");
+        for hot_loop in self.hot_loops() {
+            let _ = writeln!(
+                csv,
+                "{:03X},{:03X},{},{},{:.4}",
+                hot_loop.start,
+                hot_loop.end,
+                hot_loop.iterations,
+                hot_loop.instructions,
+                hot_loop.instructions as f64 / self.total.max(1) as f64,
+            );
+        }
+        csv
+    }
+    /// Summary of the busiest addresses, instruction patterns, subroutines and loops
+    pub fn report(&self, top: usize) -> String {
+        let share = |count: u64| 100.0 * count as f64 / self.total.max(1) as f64;
+        let mut report = format!("{} instructions\n\nBusiest addresses:\n", self.total);
+        let mut addresses: Vec<usize> = (0..self.hits.len()).filter(|address| self.hits[*address] > 0).collect();
+        addresses.sort_by_key(|address| core::cmp::Reverse(self.hits[*address]));
+        for address in addresses.into_iter().take(top) {
+            let opcode = self.opcodes[address];
+            let _ = writeln!(
+                report,
+                "  {address:03X} {opcode:04X} {:<16} {:>10} {:5.1}%",
+                disassemble(opcode).unwrap_or_default(),
+                self.hits[address],
+                share(self.hits[address]),
+            );
+        }
+        report.push_str("\nInstructions:\n");
+        let mut classes: Vec<(&str, u64)> = self.classes().collect();
+        classes.sort_by_key(|(_, count)| core::cmp::Reverse(*count));
+        for (class, count) in classes.into_iter().take(top) {
+            let _ = writeln!(report, "  {class:<8} {count:>10} {:5.1}%", share(count));
+        }
+        report.push_str("\nSubroutines:            inclusive          exclusive\n");
+        for subroutine in self.subroutines().into_iter().take(top) {
+            let _ = writeln!(
+                report,
+                "  {:<8} {:>10} {:5.1}% {:>10} {:5.1}%",
+                name(subroutine.entry),
+                subroutine.inclusive,
+                share(subroutine.inclusive),
+                subroutine.exclusive,
+                share(subroutine.exclusive),
+            );
+        }
+        report.push_str("\nLoops:\n");
+        for hot_loop in self.hot_loops().into_iter().take(top) {
+            let _ = writeln!(
+                report,
+                "  {:03X}-{:03X} {:>10} times {:>10} {:5.1}%",
+                hot_loop.start,
+                hot_loop.end,
+                hot_loop.iterations,
+                hot_loop.instructions,
+                share(hot_loop.instructions),
+            );
+        }
+        report
+    }
+}
+
+/// `sub_2A4` for a subroutine, or `main` for code outside of any
+fn name(entry: Option<u16>) -> String {
+    match entry {
+        Some(entry) => format!("sub_{entry:03X}"),
+        None => String::from("main"),
+    }
+}
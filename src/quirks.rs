@@ -0,0 +1,145 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+/// Behaviours that differ between CHIP-8 interpreters, games written for one often break on another
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    /// `8xy6`/`8xyE` shift `Vy` into `Vx` instead of shifting `Vx` in place
+    pub shift_uses_vy: bool,
+    /// `Fx55`/`Fx65` leave `I` pointing past the last register
+    pub load_store_increments_i: bool,
+    /// `8xy1`/`8xy2`/`8xy3` clear `VF`
+    pub logic_resets_vf: bool,
+    /// `Bnnn` jumps to `nnn + Vx` with `x` from the highest digit of `nnn`, instead of `nnn + V0`
+    pub jump_uses_vx: bool,
+    /// Sprites are cut off at the edges of the screen instead of wrapping around
+    pub clip_sprites: bool,
+    /// `Dxyn` waits for the next frame before the next instruction runs
+    pub display_wait: bool,
+    /// `Fx0A` finishes as soon as a key is pressed instead of once it is released
+    pub key_wait_on_press: bool,
+    /// `Fx33`/`Fx55` can overwrite the font at the start of memory
+    pub writes_font: bool,
+}
+
+impl Quirks {
+    /// Original COSMAC VIP interpreter
+    pub const VIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        logic_resets_vf: true,
+        jump_uses_vx: false,
+        clip_sprites: true,
+        display_wait: true,
+        key_wait_on_press: false,
+        writes_font: false,
+    };
+    /// SUPER-CHIP 1.1 on the HP48
+    pub const SCHIP: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        logic_resets_vf: false,
+        jump_uses_vx: true,
+        clip_sprites: true,
+        display_wait: false,
+        key_wait_on_press: true,
+        writes_font: false,
+    };
+    /// XO-CHIP as implemented by Octo
+    pub const XOCHIP: Quirks = Quirks {
+        shift_uses_vy: true,
+        load_store_increments_i: true,
+        logic_resets_vf: false,
+        jump_uses_vx: false,
+        clip_sprites: false,
+        display_wait: false,
+        key_wait_on_press: false,
+        writes_font: true,
+    };
+    /// What this interpreter has always done, and what most modern games expect
+    pub const MODERN: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: true,
+        logic_resets_vf: false,
+        jump_uses_vx: false,
+        clip_sprites: false,
+        display_wait: false,
+        key_wait_on_press: false,
+        writes_font: false,
+    };
+    /// Names accepted by `preset`
+    pub const PRESETS: [&'static str; 4] = ["modern", "vip", "schip", "xochip"];
+
+    pub fn preset(name: &str) -> Option<Quirks> {
+        match name {
+            "modern" => Some(Quirks::MODERN),
+            "vip" | "chip8" | "chip-8" => Some(Quirks::VIP),
+            "schip" | "superchip" => Some(Quirks::SCHIP),
+            "xochip" | "xo-chip" => Some(Quirks::XOCHIP),
+            _ => None,
+        }
+    }
+    /// Name of the preset these quirks match, if any
+    pub fn name(&self) -> Option<&'static str> {
+        Quirks::PRESETS
+            .into_iter()
+            .find(|name| Quirks::preset(name) == Some(*self))
+    }
+    /// A preset, or the names of the quirks that are on separated by commas like `Display` writes them
+    pub fn parse(value: &str) -> Option<Quirks> {
+        if let Some(quirks) = Quirks::preset(value) {
+            return Some(quirks);
+        }
+        let mut quirks = Quirks::NONE;
+        for name in value.split(',').map(str::trim).filter(|name| *name != "none") {
+            let (_, flag) = quirks.flags().into_iter().find(|(flag, _)| *flag == name)?;
+            *flag = true;
+        }
+        Some(quirks)
+    }
+    /// Every quirk turned off
+    const NONE: Quirks = Quirks {
+        shift_uses_vy: false,
+        load_store_increments_i: false,
+        logic_resets_vf: false,
+        jump_uses_vx: false,
+        clip_sprites: false,
+        display_wait: false,
+        key_wait_on_press: false,
+        writes_font: false,
+    };
+
+    fn flags(&mut self) -> [(&'static str, &mut bool); 8] {
+        [
+            ("shift_uses_vy", &mut self.shift_uses_vy),
+            ("load_store_increments_i", &mut self.load_store_increments_i),
+            ("logic_resets_vf", &mut self.logic_resets_vf),
+            ("jump_uses_vx", &mut self.jump_uses_vx),
+            ("clip_sprites", &mut self.clip_sprites),
+            ("display_wait", &mut self.display_wait),
+            ("key_wait_on_press", &mut self.key_wait_on_press),
+            ("writes_font", &mut self.writes_font),
+        ]
+    }
+}
+
+/// The preset name when there is one, `none` when every quirk is off
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(name) = self.name() {
+            return write!(f, "{name}");
+        }
+        let mut quirks = *self;
+        let on: Vec<&str> = quirks.flags().into_iter().filter(|(_, on)| **on).map(|(name, _)| name).collect();
+        match on.is_empty() {
+            true => write!(f, "none"),
+            false => write!(f, "{}", on.join(",")),
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Quirks::MODERN
+    }
+}
@@ -0,0 +1,346 @@
+use std::{collections::BTreeMap, fs, io, path::PathBuf};
+
+#[cfg(feature = "database")]
+use crate::database::{Database, RomInfo};
+use crate::chip8::{Chip8Interpreter, DisplayMode, Palette, DEFAULT_SPEED, FRAME_RATE, MAX_SPEED, MIN_SPEED};
+use crate::host::Keymap;
+use crate::quirks::Quirks;
+use crate::timing::Timing;
+
+/// Directory for files that persist between sessions
+pub fn config_dir() -> Option<PathBuf> {
+    let base = std::env::var_os("XDG_CONFIG_HOME")
+        .or_else(|| std::env::var_os("APPDATA"))
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(base.join("chip8"))
+}
+
+/// `key = value` lines, global defaults first and then a `[sha1]` section for every ROM with overrides.
+/// Lines are kept as written so comments and ordering survive saving
+#[derive(Clone, Debug, Default)]
+pub struct SettingsFile {
+    path: Option<PathBuf>,
+    lines: Vec<String>,
+}
+
+impl SettingsFile {
+    /// Reads `settings.cfg` from the config directory, empty when it does not exist yet
+    pub fn load() -> Self {
+        match config_dir() {
+            Some(dir) => Self::open(dir.join("settings.cfg")),
+            None => Self::default(),
+        }
+    }
+
+    pub fn open(path: PathBuf) -> Self {
+        let lines = fs::read_to_string(&path)
+            .map(|contents| contents.lines().map(String::from).collect())
+            .unwrap_or_default();
+        Self { path: Some(path), lines }
+    }
+    /// Value from the ROM's section, falling back to the global one
+    pub fn get(&self, rom: Option<&str>, key: &str) -> Option<&str> {
+        rom.and_then(|rom| self.get_in(Some(rom), key)).or_else(|| self.get_in(None, key))
+    }
+    /// Value from only the global section with `None`, or only the ROM's section
+    pub fn get_in(&self, section: Option<&str>, key: &str) -> Option<&str> {
+        self.find(section, key)
+            .map(|line| parse_line(&self.lines[line]).map_or("", |(_, value)| value))
+    }
+    /// Changes the value the ROM currently uses, which is its override when it has one and
+    /// otherwise the global value
+    pub fn set(&mut self, rom: Option<&str>, key: &str, value: &str) {
+        match rom.filter(|rom| self.find(Some(rom), key).is_some()) {
+            Some(rom) => self.set_in(Some(rom), key, value),
+            None => self.set_in(None, key, value),
+        }
+    }
+    /// Changes a value in the global section with `None` or in the ROM's section, adding the
+    /// section when needed
+    pub fn set_in(&mut self, section: Option<&str>, key: &str, value: &str) {
+        let entry = format!("{key} = {value}");
+        if let Some(line) = self.find(section, key) {
+            self.lines[line] = entry;
+            return;
+        }
+        let (start, end) = match (self.section(section), section) {
+            (Some(range), _) => range,
+            (None, Some(rom)) => {
+                if self.lines.last().is_some_and(|line| !line.trim().is_empty()) {
+                    self.lines.push(String::new());
+                }
+                self.lines.push(format!("[{rom}]"));
+                (self.lines.len(), self.lines.len())
+            }
+            (None, None) => unreachable!("the global section always exists"),
+        };
+        // New keys go after the last line of the section that is not blank
+        let end = (start..end).rev().find(|i| !self.lines[*i].trim().is_empty()).map_or(start, |i| i + 1);
+        self.lines.insert(end, entry);
+    }
+    /// Writes the file back, creating the config directory if needed
+    pub fn save(&self) -> io::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut contents = self.lines.join("\n");
+        contents.push('\n');
+        fs::write(path, contents)
+    }
+    /// Lines of the global section with `None`, or of the ROM's section, without the header
+    fn section(&self, rom: Option<&str>) -> Option<(usize, usize)> {
+        let start = match rom {
+            None => 0,
+            Some(rom) => {
+                let position = self.lines.iter().position(|line| header(line).is_some_and(|name| name.eq_ignore_ascii_case(rom)))?;
+                position + 1
+            }
+        };
+        let end = (start..self.lines.len()).find(|i| header(&self.lines[*i]).is_some()).unwrap_or(self.lines.len());
+        Some((start, end))
+    }
+
+    fn find(&self, rom: Option<&str>, key: &str) -> Option<usize> {
+        let (start, end) = self.section(rom)?;
+        (start..end).find(|i| parse_line(&self.lines[*i]).is_some_and(|(k, _)| k == key))
+    }
+}
+
+/// Name of a `[section]` header line
+fn header(line: &str) -> Option<&str> {
+    let line = line.trim();
+    line.strip_prefix('[').and_then(|line| line.strip_suffix(']')).map(str::trim)
+}
+
+fn parse_line(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim();
+    if line.starts_with('#') {
+        return None;
+    }
+    let (key, value) = line.split_once('=')?;
+    Some((key.trim(), value.trim()))
+}
+
+/// Preferences shared by every frontend, read from a `SettingsFile`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Settings {
+    /// Instructions per frame, written to the file as instructions per second
+    pub speed: u32,
+    pub quirks: Quirks,
+    pub palette: Palette,
+    pub keymap: Keymap,
+    /// From 0 for silent to 100
+    pub volume: u8,
+    pub display_mode: DisplayMode,
+    pub timing: Timing,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            speed: DEFAULT_SPEED,
+            quirks: Quirks::default(),
+            palette: Palette::default(),
+            keymap: Keymap::default(),
+            volume: 100,
+            display_mode: DisplayMode::Raw,
+            timing: Timing::Instructions,
+        }
+    }
+}
+
+impl Settings {
+    /// Keys understood by `set`
+    pub const KEYS: [&'static str; 7] = ["speed", "quirks", "palette", "keymap", "volume", "display", "timing"];
+
+    /// Defaults overridden by the global section, then by the ROM's section
+    pub fn read(file: &SettingsFile, rom: Option<&str>) -> Self {
+        let mut settings = Self::default();
+        settings.read_section(file, None);
+        if rom.is_some() {
+            settings.read_section(file, rom);
+        }
+        settings
+    }
+    /// Overrides settings with the ones from the global section with `None`, or the ROM's section
+    pub fn read_section(&mut self, file: &SettingsFile, section: Option<&str>) {
+        for key in Settings::KEYS {
+            if let Some(value) = file.get_in(section, key) {
+                if !self.set(key, value) {
+                    println!("Ignoring invalid setting: {key} = {value}");
+                }
+            }
+        }
+    }
+    /// Parses a value the same way as the file, returns false for unknown keys or invalid values
+    pub fn set(&mut self, key: &str, value: &str) -> bool {
+        let changed = match key {
+            "speed" => value.parse::<u32>().ok().map(|speed| {
+                self.speed = (speed.saturating_add(FRAME_RATE / 2) / FRAME_RATE).clamp(MIN_SPEED, MAX_SPEED)
+            }),
+            "quirks" => Quirks::parse(value).map(|quirks| self.quirks = quirks),
+            "palette" => Palette::parse(value).map(|palette| self.palette = palette),
+            "keymap" => Keymap::parse(value).map(|keymap| self.keymap = keymap),
+            "volume" => value.parse::<u8>().ok().map(|volume| self.volume = volume.min(100)),
+            "display" => DisplayMode::parse(value).map(|mode| self.display_mode = mode),
+            "timing" => Timing::parse(value).map(|timing| self.timing = timing),
+            _ => None,
+        };
+        changed.is_some()
+    }
+    /// Every setting as it would be written to the file
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("speed", (self.speed * FRAME_RATE).to_string()),
+            ("palette", self.palette.to_string()),
+            ("keymap", self.keymap.to_string()),
+            ("volume", self.volume.to_string()),
+            ("display", self.display_mode.to_string()),
+            ("timing", self.timing.to_string()),
+            ("quirks", self.quirks.to_string()),
+        ]
+    }
+
+    pub fn apply(&self, interpreter: &mut Chip8Interpreter) {
+        interpreter.set_speed(self.speed);
+        interpreter.set_quirks(self.quirks);
+        interpreter.set_palette(self.palette);
+        interpreter.set_display_mode(self.display_mode);
+        interpreter.set_timing(self.timing);
+    }
+    /// Picks up settings that hotkeys change on the interpreter
+    pub fn update(&mut self, interpreter: &Chip8Interpreter) {
+        self.speed = interpreter.speed();
+        self.display_mode = interpreter.display_mode();
+    }
+}
+
+type ExitHook = Box<dyn FnOnce(&Chip8Interpreter)>;
+
+/// Settings of a running frontend. Only values changed while running are saved, so options
+/// given on the command line are not remembered
+pub struct Session {
+    file: SettingsFile,
+    rom: Option<String>,
+    // Applied on top of the file for every ROM
+    overrides: Vec<(&'static str, String)>,
+    // Values as they were when the ROM started, anything that differs is saved
+    start: BTreeMap<&'static str, String>,
+    settings: Settings,
+    #[cfg(feature = "database")]
+    database: Option<Database>,
+    #[cfg(feature = "database")]
+    info: Option<RomInfo>,
+    // Settings the database picked for this ROM, changes to them are saved to the ROM's section
+    rom_keys: Vec<&'static str>,
+    exit_hooks: Vec<ExitHook>,
+}
+
+impl Session {
+    /// Applies the settings for the ROM the interpreter has loaded, with the overrides on top.
+    /// The ROM database is only used along with a settings file read from disk
+    pub fn new(file: SettingsFile, overrides: Vec<(&'static str, String)>, interpreter: &mut Chip8Interpreter) -> Self {
+        let mut session = Self {
+            file,
+            rom: interpreter.rom_hash().map(String::from),
+            overrides,
+            start: BTreeMap::new(),
+            settings: Settings::default(),
+            #[cfg(feature = "database")]
+            database: None,
+            #[cfg(feature = "database")]
+            info: None,
+            rom_keys: Vec::new(),
+            exit_hooks: Vec::new(),
+        };
+        // Without a settings file nothing on disk decides how the ROM runs, the database included
+        #[cfg(feature = "database")]
+        if session.file.path.is_some() {
+            session.database = Database::load(&session.file);
+        }
+        session.read();
+        session.settings.apply(interpreter);
+        session
+    }
+
+    pub fn file(&self) -> &SettingsFile {
+        &self.file
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+    /// Database entry for the loaded ROM
+    #[cfg(feature = "database")]
+    pub fn rom_info(&self) -> Option<&RomInfo> {
+        self.info.as_ref()
+    }
+    /// Title and authors of the loaded ROM when it is known
+    pub fn title(&self) -> Option<String> {
+        #[cfg(feature = "database")]
+        return self.info.as_ref().map(RomInfo::name);
+        #[cfg(not(feature = "database"))]
+        return None;
+    }
+    /// Keeps changes made to the previous ROM and applies the settings of the one just loaded
+    pub fn load(&mut self, interpreter: &mut Chip8Interpreter) {
+        self.settings.update(interpreter);
+        self.record(self.settings.entries());
+        self.rom = interpreter.rom_hash().map(String::from);
+        self.read();
+        self.settings.apply(interpreter);
+    }
+    /// Starts keeping track of values owned by the frontend, such as window options
+    pub fn track(&mut self, entries: Vec<(&'static str, String)>) {
+        self.start.extend(entries);
+    }
+    /// Runs once the frontend exits, just before settings are saved
+    pub fn on_exit(&mut self, hook: impl FnOnce(&Chip8Interpreter) + 'static) {
+        self.exit_hooks.push(Box::new(hook));
+    }
+    /// Writes back settings and tracked values that changed since they were read, called when the frontend exits
+    pub fn save(&mut self, interpreter: &Chip8Interpreter, tracked: Vec<(&'static str, String)>) -> io::Result<()> {
+        for hook in self.exit_hooks.drain(..) {
+            hook(interpreter);
+        }
+        self.settings.update(interpreter);
+        self.record(self.settings.entries());
+        self.record(tracked);
+        self.file.save()
+    }
+
+    fn read(&mut self) {
+        let rom = self.rom.as_deref();
+        self.settings = Settings::default();
+        self.settings.read_section(&self.file, None);
+        // The database knows more about a ROM than the global defaults, but less than the user
+        #[cfg(feature = "database")]
+        {
+            self.info = rom.zip(self.database.as_ref()).and_then(|(rom, database)| database.get(rom)).cloned();
+            self.rom_keys = self.info.as_ref().map(|info| info.apply(&mut self.settings)).unwrap_or_default();
+        }
+        if rom.is_some() {
+            self.settings.read_section(&self.file, rom);
+        }
+        for (key, value) in &self.overrides {
+            self.settings.set(key, value);
+        }
+        self.start.extend(self.settings.entries());
+    }
+
+    fn record(&mut self, entries: Vec<(&'static str, String)>) {
+        for (key, value) in entries {
+            if self.start.get(key) != Some(&value) {
+                match self.rom.as_deref().filter(|_| self.rom_keys.contains(&key)) {
+                    Some(rom) => self.file.set_in(Some(rom), key, &value),
+                    None => self.file.set(self.rom.as_deref(), key, &value),
+                }
+                self.start.insert(key, value);
+            }
+        }
+    }
+}
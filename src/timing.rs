@@ -0,0 +1,96 @@
+use core::fmt;
+
+/// Machine cycles of the COSMAC VIP in a 60 Hz frame, its 1.7609 MHz clock takes 8 clocks a cycle
+pub const VIP_FRAME_CYCLES: u32 = 3668;
+/// About as many cycles of each frame are taken by the display interrupt and its DMA
+const VIP_INTERRUPT_CYCLES: u32 = 1832;
+/// Cycles left to the interpreter each frame
+pub const VIP_FRAME_BUDGET: u32 = VIP_FRAME_CYCLES - VIP_INTERRUPT_CYCLES;
+/// Fetching an instruction and jumping to its routine, paid by every instruction
+const FETCH_CYCLES: u32 = 40;
+
+/// How long instructions take
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Timing {
+    /// Every instruction takes as long, the speed setting picks how many run in a frame
+    #[default]
+    Instructions,
+    /// Instructions cost what they did on the COSMAC VIP, and `Dxyn` waits for the next frame
+    Vip,
+}
+
+impl Timing {
+    /// Takes `instructions` or `vip`
+    pub fn parse(value: &str) -> Option<Timing> {
+        match value {
+            "instructions" => Some(Timing::Instructions),
+            "vip" => Some(Timing::Vip),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Timing::Instructions => write!(f, "instructions"),
+            Timing::Vip => write!(f, "vip"),
+        }
+    }
+}
+
+/// Machine cycles the VIP interpreter takes to run an instruction, from the registers and I before
+/// it runs. Counted from Laurence Scotford's disassembly of the interpreter in "Chip-8 on the COSMAC
+/// VIP" (laurencescotford.net), where every 1802 instruction takes 2 cycles and long branches 3.
+/// Whether `Ex9E` and `ExA1` skip depends on the keypad, so they are counted as not skipping
+pub fn vip_cycles(opcode: u16, registers: &[u8; 16], i: u16) -> u32 {
+    let x = ((opcode & 0x0F00) >> 8) as usize;
+    let y = ((opcode & 0x00F0) >> 4) as usize;
+    let n = (opcode & 0x000F) as u32;
+    let byte = (opcode & 0x00FF) as u8;
+    // Skipping adds another 2 to the PC, low byte first
+    let skip = |taken: bool| if taken { 4 } else { 0 };
+    // Adding to a 16 bit address takes longer when the high byte has to be carried into
+    let carry = |low: u16, add: u16| if (low & 0xFF) + add > 0xFF { 4 } else { 0 };
+    let execute = match opcode & 0xF000 {
+        0x0000 => match opcode {
+            // Zeroes the 256 bytes of display memory a byte at a time
+            0x00E0 => 24 + 3054,
+            0x00EE => 10,
+            // Machine code subroutines are not run
+            _ => 0,
+        },
+        0x1000 => 12,
+        0x2000 => 26,
+        0x3000 => 10 + skip(registers[x] == byte),
+        0x4000 => 10 + skip(registers[x] != byte),
+        0x5000 => 14 + skip(registers[x] == registers[y]),
+        0x9000 => 14 + skip(registers[x] != registers[y]),
+        0x6000 => 6,
+        0x7000 => 10,
+        // The operation is copied into RAM and run from there
+        0x8000 => 44,
+        0xA000 => 12,
+        0xB000 => 22 + carry(opcode & 0x0FFF, registers[0] as u16),
+        0xC000 => 36,
+        // Sprites not on a byte boundary are shifted and written over two bytes of every row
+        0xD000 => match registers[x] % 8 {
+            0 => 68 + n * 26,
+            _ => 68 + n * 46,
+        },
+        0xE000 => 14,
+        _ => match byte {
+            0x07 | 0x0A | 0x15 | 0x18 => 10,
+            0x1E => 12 + carry(i, registers[x] as u16),
+            0x29 => 16,
+            // Each digit is found by counting subtractions of 100, 10 and 1
+            0x33 => {
+                let value = registers[x];
+                80 + 16 * (value / 100 + value / 10 % 10 + value % 10) as u32
+            }
+            0x55 | 0x65 => 14 + 14 * (x as u32 + 1),
+            _ => 0,
+        },
+    };
+    FETCH_CYCLES + execute
+}
@@ -0,0 +1,525 @@
+use alloc::{format, string::String, vec::Vec};
+use core::fmt::{self, Write as _};
+#[cfg(feature = "std")]
+use std::{collections::VecDeque, io::{self, Write}, ops::RangeInclusive};
+
+/// Starts every binary trace, followed by fixed size records
+pub const BINARY_MAGIC: &[u8; 8] = b"CH8TRACE";
+/// Most bytes one instruction can store, `Fx55` with x = F
+pub const MAX_WRITES: usize = 16;
+/// Bytes in each binary record
+pub const RECORD_SIZE: usize = 34 + MAX_WRITES * 3;
+/// First line of a CSV trace
+pub const CSV_HEADER: &str = "cycle,pc,opcode,v0,v1,v2,v3,v4,v5,v6,v7,v8,v9,va,vb,vc,vd,ve,vf,i,sp,dt,st,writes";
+
+/// State of the machine just before an instruction runs, and the bytes it stored while running
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TraceRecord {
+    /// Instructions run since the ROM was loaded, starting from 1
+    pub cycle: u64,
+    pub pc: u16,
+    pub opcode: u16,
+    pub registers: [u8; 16],
+    pub i: u16,
+    pub sp: u8,
+    pub dt: u8,
+    pub st: u8,
+    /// Address and value of every byte the instruction stored, in order. Stores into the
+    /// protected font are left out since they never happen
+    pub writes: Vec<(u16, u8)>,
+}
+
+/// `00000001 200 00E0 V:00000000000000000000000000000000 I:000 SP:0 DT:00 ST:00`, followed by
+/// `W:300=01,301=02` when the instruction stored anything
+impl fmt::Display for TraceRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:08} {:03X} {:04X} V:", self.cycle, self.pc, self.opcode)?;
+        for v in self.registers {
+            write!(f, "{v:02X}")?;
+        }
+        write!(f, " I:{:03X} SP:{:X} DT:{:02X} ST:{:02X}", self.i, self.sp, self.dt, self.st)?;
+        if !self.writes.is_empty() {
+            write!(f, " W:{}", self.writes_list(','))?;
+        }
+        Ok(())
+    }
+}
+
+impl TraceRecord {
+    /// A line under `CSV_HEADER`, the cycle is decimal and everything else is hex
+    pub fn to_csv(&self) -> String {
+        let mut line = format!("{},{:03X},{:04X}", self.cycle, self.pc, self.opcode);
+        for v in self.registers {
+            let _ = write!(line, ",{v:02X}");
+        }
+        let _ = write!(line, ",{:03X},{:X},{:02X},{:02X},{}", self.i, self.sp, self.dt, self.st, self.writes_list(' '));
+        line
+    }
+    /// Little endian cycle, PC, opcode and I, then V0 to VF, SP, DT, ST, the number of writes and
+    /// `MAX_WRITES` slots of little endian address and value, unused ones zeroed
+    pub fn to_bytes(&self) -> [u8; RECORD_SIZE] {
+        let mut bytes = [0; RECORD_SIZE];
+        bytes[0..8].copy_from_slice(&self.cycle.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.pc.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.opcode.to_le_bytes());
+        bytes[12..14].copy_from_slice(&self.i.to_le_bytes());
+        bytes[14..30].copy_from_slice(&self.registers);
+        bytes[30] = self.sp;
+        bytes[31] = self.dt;
+        bytes[32] = self.st;
+        bytes[33] = self.writes.len().min(MAX_WRITES) as u8;
+        for (slot, (address, value)) in bytes[34..].chunks_exact_mut(3).zip(&self.writes) {
+            slot[..2].copy_from_slice(&address.to_le_bytes());
+            slot[2] = *value;
+        }
+        bytes
+    }
+    /// `300=01` for each write, separated by `separator`
+    fn writes_list(&self, separator: char) -> String {
+        let mut list = String::new();
+        for (n, (address, value)) in self.writes.iter().enumerate() {
+            if n > 0 {
+                list.push(separator);
+            }
+            let _ = write!(list, "{address:03X}={value:02X}");
+        }
+        list
+    }
+}
+
+/// Layout of each traced instruction
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// One line of text per instruction
+    #[default]
+    Text,
+    /// Comma separated with a header, for spreadsheets and scripts
+    Csv,
+    /// `BINARY_MAGIC` followed by `RECORD_SIZE` byte records
+    Binary,
+}
+
+impl TraceFormat {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "text" => Some(TraceFormat::Text),
+            "csv" => Some(TraceFormat::Csv),
+            "binary" => Some(TraceFormat::Binary),
+            _ => None,
+        }
+    }
+}
+
+/// Matches opcodes written like `Dxyn` or `8xy4`, where hex digits have to match and anything else
+/// matches any digit
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OpcodePattern {
+    mask: u16,
+    value: u16,
+}
+
+impl OpcodePattern {
+    pub fn parse(pattern: &str) -> Option<Self> {
+        if pattern.chars().count() != 4 {
+            return None;
+        }
+        let (mut mask, mut value) = (0, 0);
+        for c in pattern.chars() {
+            mask <<= 4;
+            value <<= 4;
+            if let Some(digit) = c.to_digit(16) {
+                mask |= 0xF;
+                value |= digit as u16;
+            }
+        }
+        Some(Self { mask, value })
+    }
+
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.value
+    }
+}
+
+/// When tracing starts or stops
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Trigger {
+    /// Each time the instruction at this address is about to run
+    Address(u16),
+    /// Once this many instructions have run
+    Cycle(u64),
+}
+
+impl Trigger {
+    /// A hex address, or `cycle:N` with a decimal count
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.strip_prefix("cycle:") {
+            Some(cycle) => cycle.parse().ok().map(Trigger::Cycle),
+            None => u16::from_str_radix(value.trim_start_matches("0x"), 16).ok().map(Trigger::Address),
+        }
+    }
+
+    /// Whether the trigger fires for the instruction about to run
+    pub fn hit(&self, record: &TraceRecord) -> bool {
+        match *self {
+            Trigger::Address(address) => record.pc == address,
+            Trigger::Cycle(cycle) => record.cycle == cycle,
+        }
+    }
+}
+
+/// Writes a record for every executed instruction that passes its filters
+#[cfg(feature = "std")]
+pub struct Tracer {
+    output: Box<dyn Write>,
+    format: TraceFormat,
+    ranges: Vec<RangeInclusive<u16>>,
+    opcodes: Vec<OpcodePattern>,
+    start: Option<Trigger>,
+    stop: Option<Trigger>,
+    active: bool,
+    started_output: bool,
+    /// Instruction that is running, written once it has finished
+    pending: Option<TraceRecord>,
+}
+
+#[cfg(feature = "std")]
+impl Tracer {
+    /// Traces every instruction until filters or triggers are added
+    pub fn new(output: Box<dyn Write>, format: TraceFormat) -> Self {
+        Self {
+            output,
+            format,
+            ranges: Vec::new(),
+            opcodes: Vec::new(),
+            start: None,
+            stop: None,
+            active: true,
+            started_output: false,
+            pending: None,
+        }
+    }
+    /// Only traces instructions in the given ranges of addresses
+    pub fn with_range(mut self, range: RangeInclusive<u16>) -> Self {
+        self.ranges.push(range);
+        self
+    }
+    /// Only traces instructions matching one of the given patterns
+    pub fn with_opcode(mut self, pattern: OpcodePattern) -> Self {
+        self.opcodes.push(pattern);
+        self
+    }
+    /// Waits for the trigger before tracing, the instruction that triggers it is traced
+    pub fn with_start(mut self, trigger: Trigger) -> Self {
+        self.start = Some(trigger);
+        self.active = false;
+        self
+    }
+    /// Stops tracing at the trigger until the start trigger is hit again
+    pub fn with_stop(mut self, trigger: Trigger) -> Self {
+        self.stop = Some(trigger);
+        self
+    }
+
+    /// Holds on to the state before an instruction until `finish`, collecting what it stores
+    pub(crate) fn begin(&mut self, record: TraceRecord) {
+        self.pending = Some(record);
+    }
+
+    pub(crate) fn memory_write(&mut self, address: u16, value: u8) {
+        if let Some(record) = &mut self.pending {
+            record.writes.push((address, value));
+        }
+    }
+    /// Records the instruction passed to `begin` now that it has run
+    pub(crate) fn finish(&mut self) -> io::Result<()> {
+        match self.pending.take() {
+            Some(record) => self.record(&record),
+            None => Ok(()),
+        }
+    }
+
+    pub fn record(&mut self, record: &TraceRecord) -> io::Result<()> {
+        if self.start.is_some_and(|start| start.hit(record)) {
+            self.active = true;
+        } else if self.stop.is_some_and(|stop| stop.hit(record)) {
+            self.active = false;
+        }
+        if !self.active
+            || !self.ranges.is_empty() && !self.ranges.iter().any(|range| range.contains(&record.pc))
+            || !self.opcodes.is_empty() && !self.opcodes.iter().any(|pattern| pattern.matches(record.opcode))
+        {
+            return Ok(());
+        }
+        if !self.started_output {
+            self.started_output = true;
+            match self.format {
+                TraceFormat::Text => {}
+                TraceFormat::Csv => writeln!(self.output, "{CSV_HEADER}")?,
+                TraceFormat::Binary => self.output.write_all(BINARY_MAGIC)?,
+            }
+        }
+        match self.format {
+            TraceFormat::Text => writeln!(self.output, "{record}"),
+            TraceFormat::Csv => writeln!(self.output, "{}", record.to_csv()),
+            TraceFormat::Binary => self.output.write_all(&record.to_bytes()),
+        }
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+impl TraceRecord {
+    /// Reads a line written by the text format, `None` for anything else
+    pub fn parse_text(line: &str) -> Option<Self> {
+        let mut fields = line.split_whitespace();
+        let mut next = |prefix: &str| fields.next()?.strip_prefix(prefix);
+        let cycle = next("")?.parse().ok()?;
+        let pc = hex(next("")?)?;
+        let opcode = hex(next("")?)?;
+        let registers = registers(next("V:")?)?;
+        Some(Self {
+            cycle,
+            pc,
+            opcode,
+            registers,
+            i: hex(next("I:")?)?,
+            sp: hex(next("SP:")?)? as u8,
+            dt: hex(next("DT:")?)? as u8,
+            st: hex(next("ST:")?)? as u8,
+            writes: match fields.next() {
+                Some(writes) => parse_writes(writes.strip_prefix("W:")?, ',')?,
+                None => Vec::new(),
+            },
+        })
+    }
+    /// Reads a line written by the CSV format, `None` for the header or anything else
+    pub fn parse_csv(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.trim().split(',').collect();
+        let [cycle, pc, opcode, rest @ ..] = fields.as_slice() else {
+            return None;
+        };
+        let [v @ .., i, sp, dt, st, writes] = rest else {
+            return None;
+        };
+        let mut registers = [0; 16];
+        if v.len() != 16 {
+            return None;
+        }
+        for (register, value) in registers.iter_mut().zip(v) {
+            *register = hex(value)? as u8;
+        }
+        Some(Self {
+            cycle: cycle.parse().ok()?,
+            pc: hex(pc)?,
+            opcode: hex(opcode)?,
+            registers,
+            i: hex(i)?,
+            sp: hex(sp)? as u8,
+            dt: hex(dt)? as u8,
+            st: hex(st)? as u8,
+            writes: parse_writes(writes, ' ')?,
+        })
+    }
+
+    pub fn from_bytes(bytes: &[u8; RECORD_SIZE]) -> Self {
+        let word = |at: usize| u16::from_le_bytes([bytes[at], bytes[at + 1]]);
+        let mut cycle = [0; 8];
+        cycle.copy_from_slice(&bytes[0..8]);
+        let mut registers = [0; 16];
+        registers.copy_from_slice(&bytes[14..30]);
+        let count = (bytes[33] as usize).min(MAX_WRITES);
+        let writes = bytes[34..].chunks_exact(3).take(count).map(|slot| (u16::from_le_bytes([slot[0], slot[1]]), slot[2]));
+        Self {
+            cycle: u64::from_le_bytes(cycle),
+            pc: word(8),
+            opcode: word(10),
+            i: word(12),
+            registers,
+            sp: bytes[30],
+            dt: bytes[31],
+            st: bytes[32],
+            writes: writes.collect(),
+        }
+    }
+    /// What differs from another record of the same cycle, such as `V3 05 vs 06`
+    pub fn differences(&self, other: &Self, timers: bool) -> Vec<String> {
+        let mut differences = Vec::new();
+        if self.pc != other.pc {
+            differences.push(format!("PC {:03X} vs {:03X}", self.pc, other.pc));
+        }
+        if self.opcode != other.opcode {
+            differences.push(format!("opcode {:04X} vs {:04X}", self.opcode, other.opcode));
+        }
+        for (n, (a, b)) in self.registers.iter().zip(other.registers).enumerate() {
+            if *a != b {
+                differences.push(format!("V{n:X} {a:02X} vs {b:02X}"));
+            }
+        }
+        if self.i != other.i {
+            differences.push(format!("I {:03X} vs {:03X}", self.i, other.i));
+        }
+        if self.sp != other.sp {
+            differences.push(format!("SP {:X} vs {:X}", self.sp, other.sp));
+        }
+        if timers && self.dt != other.dt {
+            differences.push(format!("DT {:02X} vs {:02X}", self.dt, other.dt));
+        }
+        if timers && self.st != other.st {
+            differences.push(format!("ST {:02X} vs {:02X}", self.st, other.st));
+        }
+        if self.writes != other.writes {
+            let list = |record: &Self| match record.writes.is_empty() {
+                true => String::from("none"),
+                false => record.writes_list(','),
+            };
+            differences.push(format!("writes {} vs {}", list(self), list(other)));
+        }
+        differences
+    }
+}
+
+fn hex(value: &str) -> Option<u16> {
+    u16::from_str_radix(value, 16).ok()
+}
+
+/// `300=01` pairs split by `separator`, nothing at all for none
+fn parse_writes(value: &str, separator: char) -> Option<Vec<(u16, u8)>> {
+    let value = value.trim();
+    if value.is_empty() {
+        return Some(Vec::new());
+    }
+    value
+        .split(separator)
+        .map(|write| {
+            let (address, value) = write.split_once('=')?;
+            Some((hex(address)?, u8::from_str_radix(value, 16).ok()?))
+        })
+        .collect()
+}
+
+fn registers(value: &str) -> Option<[u8; 16]> {
+    if value.len() != 32 || !value.is_ascii() {
+        return None;
+    }
+    let mut registers = [0; 16];
+    for (n, register) in registers.iter_mut().enumerate() {
+        *register = u8::from_str_radix(&value[n * 2..n * 2 + 2], 16).ok()?;
+    }
+    Some(registers)
+}
+
+/// Reads records back from a trace in any of the formats, telling them apart by how they start.
+/// Lines of text that are not records are skipped
+#[cfg(feature = "std")]
+pub struct TraceReader<R> {
+    input: R,
+    format: TraceFormat,
+    line: String,
+}
+
+#[cfg(feature = "std")]
+impl<R: io::BufRead> TraceReader<R> {
+    pub fn new(mut input: R) -> io::Result<Self> {
+        let start = input.fill_buf()?;
+        let format = if start.starts_with(BINARY_MAGIC) {
+            input.consume(BINARY_MAGIC.len());
+            TraceFormat::Binary
+        } else if start.starts_with(CSV_HEADER.as_bytes()) {
+            TraceFormat::Csv
+        } else {
+            TraceFormat::Text
+        };
+        Ok(Self { input, format, line: String::new() })
+    }
+
+    pub fn format(&self) -> TraceFormat {
+        self.format
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: io::BufRead> Iterator for TraceReader<R> {
+    type Item = io::Result<TraceRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.format == TraceFormat::Binary {
+            let mut bytes = [0; RECORD_SIZE];
+            return match self.input.read_exact(&mut bytes) {
+                Ok(()) => Some(Ok(TraceRecord::from_bytes(&bytes))),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => None,
+                Err(e) => Some(Err(e)),
+            };
+        }
+        loop {
+            self.line.clear();
+            match self.input.read_line(&mut self.line) {
+                Ok(0) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e)),
+            }
+            let record = match self.format {
+                TraceFormat::Csv => TraceRecord::parse_csv(&self.line),
+                _ => TraceRecord::parse_text(&self.line),
+            };
+            if let Some(record) = record {
+                return Some(Ok(record));
+            }
+        }
+    }
+}
+
+/// Where two traces first disagree
+#[derive(Clone, Debug)]
+pub struct Divergence {
+    pub cycle: u64,
+    pub a: TraceRecord,
+    pub b: TraceRecord,
+    pub differences: Vec<String>,
+    /// Records both traces agree on just before the divergence, oldest first. The last one is
+    /// kept even without asking for context, since it is usually the instruction at fault
+    pub context: Vec<TraceRecord>,
+}
+
+/// Walks two traces side by side by cycle, returning the first record that differs. Cycles only
+/// one trace has are skipped, so traces written with different filters or triggers can still be
+/// compared where they overlap, and the comparison ends with the shorter trace. Timers can be left
+/// out of the comparison since they depend on how fast each emulator runs
+#[cfg(feature = "std")]
+pub fn first_divergence(
+    mut a: impl Iterator<Item = io::Result<TraceRecord>>,
+    mut b: impl Iterator<Item = io::Result<TraceRecord>>,
+    timers: bool,
+    context: usize,
+) -> io::Result<Option<Divergence>> {
+    let context = context.max(1);
+    let mut previous = VecDeque::with_capacity(context);
+    let (mut next_a, mut next_b) = (a.next().transpose()?, b.next().transpose()?);
+    while let (Some(record_a), Some(record_b)) = (&next_a, &next_b) {
+        if record_a.cycle < record_b.cycle {
+            next_a = a.next().transpose()?;
+            continue;
+        }
+        if record_b.cycle < record_a.cycle {
+            next_b = b.next().transpose()?;
+            continue;
+        }
+        let differences = record_a.differences(record_b, timers);
+        if !differences.is_empty() {
+            return Ok(Some(Divergence {
+                cycle: record_a.cycle,
+                a: record_a.clone(),
+                b: record_b.clone(),
+                differences,
+                context: previous.into(),
+            }));
+        }
+        if previous.len() == context {
+            previous.pop_front();
+        }
+        previous.push_back(record_a.clone());
+        (next_a, next_b) = (a.next().transpose()?, b.next().transpose()?);
+    }
+    Ok(None)
+}
@@ -0,0 +1,330 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Stdout, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show},
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute, queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
+};
+
+use crate::chip8::{Chip8Interpreter, Palette, FRAME_RATE, HEIGHT, WIDTH};
+use crate::host::{Audio, Command, Display, Input, InputEvent, Keymap, Machine, SleepClock};
+use crate::osd::Osd;
+use crate::settings::Session;
+
+/// Terminals only report presses, so a key counts as held this long after its first press
+const FIRST_PRESS_HOLD: Duration = Duration::from_millis(400);
+/// and this long after every auto repeat, which arrive much faster once they start
+const REPEAT_HOLD: Duration = Duration::from_millis(100);
+
+/// Characters used to draw pixels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Glyphs {
+    /// Two pixels per cell stacked vertically, drawn as 64x16 cells in colour
+    HalfBlock,
+    /// Eight pixels per cell as 2x4 dots, drawn as 32x8 cells
+    Braille,
+}
+
+/// Draws frames to the terminal
+pub struct TerminalDisplay {
+    out: Stdout,
+    glyphs: Glyphs,
+    // Braille has no colours, dots are lit where the frame is closer to the lit colour
+    palette: Palette,
+    // Last frame drawn, unchanged frames are not sent again
+    last: Vec<u8>,
+    // Readout and latest toast shown on the line below the picture
+    status: String,
+}
+
+impl TerminalDisplay {
+    fn draw_half_blocks(&mut self, frame: &[u8]) -> io::Result<()> {
+        let colour = |x: usize, y: usize| {
+            let index = (y * WIDTH + x) * 4;
+            Color::Rgb { r: frame[index], g: frame[index + 1], b: frame[index + 2] }
+        };
+        for row in 0..HEIGHT / 2 {
+            queue!(self.out, MoveTo(0, row as u16))?;
+            // Only send colours when they change from the previous cell
+            let mut current = None;
+            for x in 0..WIDTH {
+                let colours = (colour(x, row * 2), colour(x, row * 2 + 1));
+                if current != Some(colours) {
+                    queue!(self.out, SetForegroundColor(colours.0), SetBackgroundColor(colours.1))?;
+                    current = Some(colours);
+                }
+                queue!(self.out, Print('▀'))?;
+            }
+        }
+        queue!(self.out, ResetColor)
+    }
+
+    fn draw_braille(&mut self, frame: &[u8]) -> io::Result<()> {
+        for (row, line) in braille_lines(frame, &self.palette).into_iter().enumerate() {
+            queue!(self.out, MoveTo(0, row as u16), Print(line))?;
+        }
+        Ok(())
+    }
+
+    fn draw_status(&mut self, status: &str) -> io::Result<()> {
+        let row = match self.glyphs {
+            Glyphs::HalfBlock => HEIGHT / 2,
+            Glyphs::Braille => HEIGHT / 4,
+        };
+        let line: String = status.chars().chain(std::iter::repeat(' ')).take(WIDTH).collect();
+        queue!(self.out, MoveTo(0, row as u16), Print(line))
+    }
+}
+
+impl Display for TerminalDisplay {
+    fn present(&mut self, frame: &[u8], osd: &Osd) -> bool {
+        let status = [osd.readout_text(), osd.toasts().last().map(String::from)]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join("  ");
+        if self.last == frame && self.status == status {
+            return true;
+        }
+        let mut result = Ok(());
+        if self.last != frame {
+            self.last.clear();
+            self.last.extend_from_slice(frame);
+            result = match self.glyphs {
+                Glyphs::HalfBlock => self.draw_half_blocks(frame),
+                Glyphs::Braille => self.draw_braille(frame),
+            };
+        }
+        if self.status != status {
+            result = result.and_then(|_| self.draw_status(&status));
+            self.status = status;
+        }
+        result.and_then(|_| self.out.flush()).is_ok()
+    }
+}
+
+/// Rows of braille characters for a 64x32 RGBA frame, a dot is raised where the pixel is nearer
+/// `palette.on` than `palette.off` so light on dark and dark on light palettes both work
+pub fn braille_lines(frame: &[u8], palette: &Palette) -> Vec<String> {
+    // Bit of each dot in a braille character, indexed by [y][x] within the cell
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+    let distance = |index: usize, colour: [u8; 3]| {
+        (0..3).map(|i| (frame[index + i] as i32 - colour[i] as i32).pow(2)).sum::<i32>()
+    };
+    let lit = |x: usize, y: usize| {
+        let index = (y * WIDTH + x) * 4;
+        distance(index, palette.on) < distance(index, palette.off)
+    };
+    (0..HEIGHT / 4)
+        .map(|row| {
+            (0..WIDTH / 2)
+                .map(|column| {
+                    let mut dots = 0;
+                    for (dy, bits) in DOTS.iter().enumerate() {
+                        for (dx, bit) in bits.iter().enumerate() {
+                            if lit(column * 2 + dx, row * 4 + dy) {
+                                dots |= bit;
+                            }
+                        }
+                    }
+                    char::from_u32(0x2800 + dots).unwrap_or(' ')
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Reads keys from the terminal, emulating releases when the terminal cannot report them
+pub struct TerminalInput {
+    pending: VecDeque<InputEvent>,
+    // When each held hex key will be released, unused when real releases are reported
+    held: [Option<Instant>; 16],
+    fast_forward_until: Option<Instant>,
+    keymap: Keymap,
+    reports_releases: bool,
+    read_this_frame: bool,
+}
+
+impl TerminalInput {
+    fn read_terminal(&mut self) {
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            match event::read() {
+                Ok(Event::Key(key)) => self.handle_key(key),
+                Ok(Event::FocusGained) => self.pending.push_back(InputEvent::Focus(true)),
+                Ok(Event::FocusLost) => self.pending.push_back(InputEvent::Focus(false)),
+                Ok(_) => {}
+                Err(_) => break,
+            }
+        }
+        if self.reports_releases {
+            return;
+        }
+        let now = Instant::now();
+        for (position, held) in self.held.iter_mut().enumerate() {
+            if held.is_some_and(|until| until <= now) {
+                *held = None;
+                self.pending.push_back(InputEvent::Key(position, false));
+            }
+        }
+        if self.fast_forward_until.is_some_and(|until| until <= now) {
+            self.fast_forward_until = None;
+            self.pending.push_back(InputEvent::Command(Command::FastForward(false)));
+        }
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        if key.code == KeyCode::Esc
+            || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL))
+        {
+            self.pending.push_back(InputEvent::Command(Command::Quit));
+            return;
+        }
+        let pressed = key.kind != KeyEventKind::Release;
+        let hold = match key.kind {
+            KeyEventKind::Repeat => REPEAT_HOLD,
+            _ => FIRST_PRESS_HOLD,
+        };
+        if let KeyCode::Char(c) = key.code {
+            if let Some(position) = self.keymap.key(c) {
+                if self.reports_releases {
+                    self.pending.push_back(InputEvent::Key(position, pressed));
+                } else {
+                    // Repeats keep the key held without pressing it again
+                    if self.held[position].is_none() {
+                        self.pending.push_back(InputEvent::Key(position, true));
+                    }
+                    self.held[position] = Some(Instant::now() + hold);
+                }
+                return;
+            }
+        }
+        if key.code == KeyCode::Char(' ') {
+            if !self.reports_releases {
+                self.fast_forward_until = Some(Instant::now() + hold);
+            }
+            self.pending.push_back(InputEvent::Command(Command::FastForward(pressed)));
+            return;
+        }
+        if key.kind != KeyEventKind::Press {
+            return;
+        }
+        let command = match key.code {
+            KeyCode::Char('-') => Command::SpeedDown,
+            KeyCode::Char('=') => Command::SpeedUp,
+            KeyCode::Tab => Command::CycleDisplayMode,
+            KeyCode::Char('p') => Command::TogglePause,
+            KeyCode::Backspace => Command::Reset,
+            KeyCode::Char('n') => Command::FrameAdvance,
+            KeyCode::Char('m') => Command::ToggleSlowMotion,
+            KeyCode::Char('i') => Command::ToggleReadout,
+            _ => return,
+        };
+        self.pending.push_back(InputEvent::Command(command));
+    }
+}
+
+impl Input for TerminalInput {
+    fn poll(&mut self) -> Option<InputEvent> {
+        if !self.read_this_frame {
+            self.read_terminal();
+            self.read_this_frame = true;
+        }
+        let event = self.pending.pop_front();
+        // `None` ends the frame, read the terminal again on the next one
+        self.read_this_frame = event.is_some();
+        event
+    }
+}
+
+/// Rings the terminal bell whenever the tone starts
+pub struct TerminalBell {
+    on: bool,
+    muted: bool,
+}
+
+impl Audio for TerminalBell {
+    fn set_tone(&mut self, on: bool) {
+        if on && !self.on && !self.muted {
+            print!("\x07");
+            let _ = io::stdout().flush();
+        }
+        self.on = on;
+    }
+}
+
+/// Puts the terminal in raw mode on the alternate screen, restoring it when dropped
+struct TerminalGuard {
+    enhanced: bool,
+}
+
+impl TerminalGuard {
+    fn new() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut out = io::stdout();
+        execute!(out, EnterAlternateScreen, Hide, Clear(ClearType::All), event::EnableFocusChange)?;
+        let enhanced = matches!(terminal::supports_keyboard_enhancement(), Ok(true));
+        if enhanced {
+            execute!(out, PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES))?;
+        }
+        Ok(Self { enhanced })
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let mut out = io::stdout();
+        if self.enhanced {
+            let _ = execute!(out, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(out, event::DisableFocusChange, ResetColor, Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Runs the interpreter in the terminal until escape or ctrl+c is pressed, then saves what
+/// changed to the session's settings
+pub fn run(interpreter: Chip8Interpreter, glyphs: Glyphs, mut session: Session) -> io::Result<()> {
+    let guard = TerminalGuard::new()?;
+    if let Some(title) = session.title() {
+        execute!(io::stdout(), SetTitle(title))?;
+    }
+    // Windows always reports releases, other platforms only with the enhancement
+    let reports_releases = guard.enhanced || cfg!(windows);
+    let display = TerminalDisplay {
+        out: io::stdout(),
+        glyphs,
+        palette: interpreter.palette(),
+        last: Vec::new(),
+        status: String::new(),
+    };
+    let input = TerminalInput {
+        pending: VecDeque::new(),
+        held: [None; 16],
+        fast_forward_until: None,
+        keymap: session.settings().keymap,
+        reports_releases,
+        read_this_frame: false,
+    };
+    let mut machine = Machine::new(
+        interpreter,
+        display,
+        input,
+        TerminalBell { on: false, muted: session.settings().volume == 0 },
+        SleepClock::new(Duration::from_secs(1) / FRAME_RATE),
+    );
+    machine.osd_mut().set_readout(session.file().get(None, "readout") == Some("true"));
+    session.track(vec![("readout", machine.osd().readout().to_string())]);
+    machine.run();
+    drop(guard);
+    session.save(machine.interpreter(), vec![("readout", machine.osd().readout().to_string())])
+}
+
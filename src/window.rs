@@ -1,23 +1,282 @@
-use std::{path::PathBuf, time::{Duration, Instant}};
+use std::{collections::VecDeque, fs, path::{Path, PathBuf}, time::Duration};
 
 use pixels::{wgpu::Color, Pixels, PixelsBuilder, SurfaceTexture};
 use winit::event::{ElementState, VirtualKeyCode as VKC};
-use rodio::{Device, Source};
 use winit::{
-    dpi::LogicalSize,
+    dpi::{LogicalSize, PhysicalSize},
     event::{Event, WindowEvent},
-    event_loop::EventLoop,
-    window::{Window, WindowBuilder},
+    event_loop::{ControlFlow, EventLoop},
+    window::{Fullscreen, Window, WindowBuilder},
 };
-use crate::chip8::{HEIGHT, WIDTH};
+#[cfg(feature = "audio")]
+use crate::audio::Beeper;
+use crate::chip8::{parse_colour, Chip8Interpreter, FRAME_RATE, HEIGHT, WIDTH};
+use crate::debugger::Console;
+use crate::host::{Command, Display, InputEvent, Machine, SleepClock};
+use crate::menu::{add_recent, MenuKey, RomMenu};
+use crate::osd::Osd;
+use crate::settings::{Session, SettingsFile};
+use crate::text::Canvas;
 use std::io::Write;
 
-/// An easy way to interact with window, pixel buffer and audio
-pub struct Chip8Window {
+const TITLE: &str = "CHIP-8 Interpreter";
+
+/// How the 64x32 frame is fit into the window
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalingMode {
+    /// Largest whole number scale that fits, every pixel is the same size
+    PixelPerfect,
+    /// Largest scale that fits while keeping the aspect ratio
+    Fit,
+    /// Fill the whole window, ignoring the aspect ratio
+    Stretch,
+}
+
+impl ScalingMode {
+    pub fn next(self) -> Self {
+        match self {
+            ScalingMode::PixelPerfect => ScalingMode::Fit,
+            ScalingMode::Fit => ScalingMode::Stretch,
+            ScalingMode::Stretch => ScalingMode::PixelPerfect,
+        }
+    }
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "pixel" | "pixel-perfect" => Some(ScalingMode::PixelPerfect),
+            "fit" => Some(ScalingMode::Fit),
+            "stretch" => Some(ScalingMode::Stretch),
+            _ => None,
+        }
+    }
+    pub fn name(self) -> &'static str {
+        match self {
+            ScalingMode::PixelPerfect => "pixel",
+            ScalingMode::Fit => "fit",
+            ScalingMode::Stretch => "stretch",
+        }
+    }
+}
+
+/// Window presentation options, remembered between sessions
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowOptions {
+    pub fullscreen: bool,
+    /// Fixed whole number scale, `None` scales with the window
+    pub scale: Option<u32>,
+    pub scaling: ScalingMode,
+    /// Colour of the area around the frame
+    pub border: [u8; 3],
+    /// Logical size of the window when not fullscreen
+    pub size: (u32, u32),
+}
+
+impl Default for WindowOptions {
+    fn default() -> Self {
+        Self {
+            fullscreen: false,
+            scale: None,
+            scaling: ScalingMode::PixelPerfect,
+            border: [13, 13, 13],
+            size: (WIDTH as u32 * 4, HEIGHT as u32 * 4),
+        }
+    }
+}
+
+impl WindowOptions {
+    /// Reads the options from the global section of the settings file, falling back to defaults
+    pub fn read(file: &SettingsFile) -> Self {
+        let mut options = Self::default();
+        let get = |key| file.get(None, key);
+        if let Some(value) = get("fullscreen") {
+            options.fullscreen = value == "true";
+        }
+        if let Some(value) = get("scale") {
+            options.scale = value.parse().ok().filter(|scale| *scale > 0);
+        }
+        if let Some(scaling) = get("scaling").and_then(ScalingMode::parse) {
+            options.scaling = scaling;
+        }
+        if let Some(border) = get("border").and_then(parse_colour) {
+            options.border = border;
+        }
+        if let Some((width, height)) = get("size").and_then(|value| value.split_once('x')) {
+            if let (Ok(width), Ok(height)) = (width.parse(), height.parse()) {
+                options.size = (width, height);
+            }
+        }
+        options
+    }
+    /// Every option as it is written to the settings file
+    pub fn entries(&self) -> Vec<(&'static str, String)> {
+        let [r, g, b] = self.border;
+        vec![
+            ("fullscreen", self.fullscreen.to_string()),
+            ("scale", self.scale.map(|scale| scale.to_string()).unwrap_or_default()),
+            ("scaling", self.scaling.name().to_string()),
+            ("border", format!("{r:02x}{g:02x}{b:02x}")),
+            ("size", format!("{}x{}", self.size.0, self.size.1)),
+        ]
+    }
+}
+
+/// Area of the window the frame is drawn to, as x, y, width and height
+pub fn frame_rect(options: &WindowOptions, target: (usize, usize)) -> (usize, usize, usize, usize) {
+    let (target_width, target_height) = target;
+    let (width, height) = match (options.scale, options.scaling) {
+        (Some(scale), _) => (WIDTH * scale as usize, HEIGHT * scale as usize),
+        (None, ScalingMode::PixelPerfect) => {
+            let scale = (target_width / WIDTH).min(target_height / HEIGHT).max(1);
+            (WIDTH * scale, HEIGHT * scale)
+        }
+        (None, ScalingMode::Fit) => {
+            let scale = (target_width as f32 / WIDTH as f32).min(target_height as f32 / HEIGHT as f32);
+            ((WIDTH as f32 * scale) as usize, (HEIGHT as f32 * scale) as usize)
+        }
+        (None, ScalingMode::Stretch) => (target_width, target_height),
+    };
+    let (width, height) = (width.clamp(1, target_width.max(1)), height.clamp(1, target_height.max(1)));
+    ((target_width - width.min(target_width)) / 2, (target_height - height.min(target_height)) / 2, width, height)
+}
+
+/// Scales the 64x32 RGBA frame into the target buffer, filling the rest with the border colour
+pub fn blit_frame(frame: &[u8], target: &mut [u8], target_width: usize, options: &WindowOptions) {
+    debug_assert!(frame.len() == HEIGHT * WIDTH * 4);
+    if target_width == 0 {
+        return;
+    }
+    let target_height = target.len() / 4 / target_width;
+    let (x, y, width, height) = frame_rect(options, (target_width, target_height));
+    let [r, g, b] = options.border;
+    let border = [r, g, b, 255];
+    for (row, out) in target.chunks_exact_mut(target_width * 4).enumerate() {
+        if row < y || row >= y + height {
+            for pixel in out.chunks_exact_mut(4) {
+                pixel.copy_from_slice(&border);
+            }
+            continue;
+        }
+        let source_y = (row - y) * HEIGHT / height;
+        for (column, pixel) in out.chunks_exact_mut(4).enumerate() {
+            if column < x || column >= x + width {
+                pixel.copy_from_slice(&border);
+            } else {
+                let source_x = (column - x) * WIDTH / width;
+                let index = (source_y * WIDTH + source_x) * 4;
+                pixel.copy_from_slice(&frame[index..index + 4]);
+            }
+        }
+    }
+}
+
+/// The window and its pixel buffer, shows frames scaled to the window
+pub struct WindowDisplay {
     surface: Pixels,
     window: Window,
+    options: WindowOptions,
+    buffer_width: usize,
+    // Shown instead of the game while open
+    menu: Option<RomMenu>,
+    // Whether the game was running when the menu opened, so closing it resumes the game
+    resume_after_menu: bool,
+}
+
+impl WindowDisplay {
+    /// Keeps the pixel buffer at the same resolution as the window
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.surface
+            .resize_surface(size.width, size.height)
+            .expect("Could not resize surface");
+        self.surface
+            .resize_buffer(size.width, size.height)
+            .expect("Could not resize buffer");
+        self.buffer_width = size.width as usize;
+        if !self.options.fullscreen {
+            let size = size.to_logical(self.window.scale_factor());
+            self.options.size = (size.width, size.height);
+        }
+    }
+    /// Shows the ROM menu over the game, pausing it until the menu is closed
+    fn open_menu(&mut self, interpreter: &mut Chip8Interpreter, directory: Option<PathBuf>) {
+        if self.menu.is_none() {
+            self.resume_after_menu = !interpreter.is_paused();
+            interpreter.pause();
+        }
+        self.menu = Some(RomMenu::new(directory));
+    }
+    /// Goes back to the game, only possible once a ROM is loaded
+    fn close_menu(&mut self, interpreter: &mut Chip8Interpreter) {
+        if interpreter.rom_hash().is_none() {
+            return;
+        }
+        if self.menu.take().is_some() && self.resume_after_menu {
+            interpreter.resume();
+        }
+    }
+    /// Window hotkeys, these are handled here and never reach the interpreter.
+    /// Returns a message to show, or `None` when the key is not a hotkey
+    fn handle_hotkey(&mut self, key: VKC) -> Option<String> {
+        let message = match key {
+            VKC::F11 => {
+                self.options.fullscreen = !self.options.fullscreen;
+                self.window.set_fullscreen(self.options.fullscreen.then_some(Fullscreen::Borderless(None)));
+                format!("Fullscreen {}", if self.options.fullscreen { "on" } else { "off" })
+            }
+            VKC::F10 => {
+                self.options.scale = None;
+                self.options.scaling = self.options.scaling.next();
+                format!("Scaling: {}", self.options.scaling.name())
+            }
+            VKC::F8 | VKC::F9 => {
+                let scale = match self.options.scale {
+                    Some(scale) if key == VKC::F8 => scale.saturating_sub(1).max(1),
+                    Some(scale) => scale + 1,
+                    None => (self.options.size.0 / WIDTH as u32).max(1),
+                };
+                self.options.scale = Some(scale);
+                if !self.options.fullscreen {
+                    self.window.set_inner_size(LogicalSize::new(WIDTH as u32 * scale, HEIGHT as u32 * scale));
+                }
+                format!("Scale: {scale}x")
+            }
+            _ => return None,
+        };
+        Some(message)
+    }
+}
+
+impl Display for WindowDisplay {
+    fn present(&mut self, frame: &[u8], osd: &Osd) -> bool {
+        match &self.menu {
+            Some(menu) => menu.draw(&mut Canvas::new(self.surface.frame_mut(), self.buffer_width), self.options.border),
+            None => blit_frame(frame, self.surface.frame_mut(), self.buffer_width, &self.options),
+        }
+        let mut canvas = Canvas::new(self.surface.frame_mut(), self.buffer_width);
+        // Sized so text stays readable without covering much of a small window
+        let scale = (canvas.height() / 240).max(1);
+        osd.draw(&mut canvas, scale);
+        if let Err(e) = self.surface.render() {
+            println!("{e}");
+            return false;
+        }
+        true
+    }
+}
+
+#[cfg(feature = "audio")]
+type WindowAudio = Option<Beeper>;
+#[cfg(not(feature = "audio"))]
+type WindowAudio = ();
+
+type WindowMachine = Machine<WindowDisplay, VecDeque<InputEvent>, WindowAudio, SleepClock>;
+
+/// An easy way to interact with window, pixel buffer and audio
+pub struct Chip8Window {
+    display: WindowDisplay,
     event_loop: EventLoop<()>,
-    audio: Device,
+    console: Option<Console>,
 }
 impl Default for Chip8Window {
     fn default() -> Self {
@@ -27,23 +286,31 @@ impl Default for Chip8Window {
 
 impl Chip8Window {
     pub fn new() -> Self {
-        let (width, height) = (WIDTH as u32, HEIGHT as u32);
+        Self::with_options(WindowOptions::read(&SettingsFile::load()))
+    }
+    pub fn with_options(options: WindowOptions) -> Self {
         let event_loop = EventLoop::new();
 
         let window = {
-            let size = LogicalSize::new(width * 4, height * 4);
+            let size = match options.scale {
+                Some(scale) => LogicalSize::new(WIDTH as u32 * scale, HEIGHT as u32 * scale),
+                None => LogicalSize::new(options.size.0, options.size.1),
+            };
             WindowBuilder::new()
-                .with_title("CHIP-8 Interpreter")
+                .with_title(TITLE)
                 .with_inner_size(size)
-                .with_min_inner_size(size)
+                .with_min_inner_size(LogicalSize::new(WIDTH as u32, HEIGHT as u32))
+                .with_fullscreen(options.fullscreen.then_some(Fullscreen::Borderless(None)))
                 .build(&event_loop)
                 .expect("Could not create window")
         };
 
+        let size = window.inner_size();
         let surface = {
-            let surface_texture = SurfaceTexture::new(width, height, &window);
+            let surface_texture = SurfaceTexture::new(size.width, size.height, &window);
+            // The whole buffer is drawn to, this only shows while resizing
             const DARKNESS: f64 = 0.05;
-            PixelsBuilder::new(width, height, surface_texture)
+            PixelsBuilder::new(size.width.max(1), size.height.max(1), surface_texture)
                 .clear_color(Color {
                     r: DARKNESS,
                     g: DARKNESS,
@@ -53,105 +320,249 @@ impl Chip8Window {
                 .build()
                 .expect("Could not create surface")
         };
-        let audio = rodio::default_output_device().unwrap();
         Self {
-            surface,
-            window,
+            display: WindowDisplay {
+                surface,
+                window,
+                options,
+                buffer_width: size.width.max(1) as usize,
+                menu: None,
+                resume_after_menu: false,
+            },
             event_loop,
-            audio,
+            console: None,
         }
     }
-    pub fn run<F>(mut self, mut func: F)
-    where
-        F: 'static + FnMut(GameEvents, &mut [u8]) -> bool,
-    {
-        let target_ms = 1_000_000f32 /
-            self.window.primary_monitor()
-                .and_then(|monitor| monitor.refresh_rate_millihertz())
-                .unwrap_or(60_000) as f32;
-        
-        let mut last_draw = Instant::now();
+    /// Takes debugger commands from stdin while the window is open
+    pub fn with_console(mut self, console: Console) -> Self {
+        self.console = Some(console);
+        self
+    }
+    /// Runs the interpreter until the window is closed, then saves what changed to the session's settings
+    pub fn run(self, interpreter: Chip8Interpreter, mut session: Session) {
+        // Speed and timers count in frames of `FRAME_RATE`, whatever rate the monitor refreshes at.
+        // Vsync only decides when a frame is presented
+        let frame_length = Duration::from_secs(1) / FRAME_RATE;
+
+        #[cfg(feature = "audio")]
+        let mut audio: WindowAudio = Beeper::new(frame_length);
+        #[cfg(not(feature = "audio"))]
+        let mut audio: WindowAudio = ();
+        set_volume(&mut audio, session.settings().volume);
+
+        session.track(self.display.options.entries());
+        set_title(&self.display.window, &session);
+
+        let mut machine = Machine::new(
+            interpreter,
+            self.display,
+            VecDeque::new(),
+            audio,
+            SleepClock::new(frame_length),
+        );
+        let rom_dir = session.file().get(None, "rom_dir").map(PathBuf::from);
+        machine.osd_mut().set_readout(session.file().get(None, "readout") == Some("true"));
+        session.track(vec![("readout", machine.osd().readout().to_string())]);
+        // Nothing to play yet, so start by picking a ROM
+        if machine.interpreter().rom_hash().is_none() {
+            let (interpreter, display) = machine.interpreter_and_display_mut();
+            display.open_menu(interpreter, rom_dir.clone());
+        }
 
+        let mut console = self.console;
         self.event_loop.run(move |event, _, control_flow| {
-            if let Event::WindowEvent { event: WindowEvent::CloseRequested, .. } = event {
-                control_flow.set_exit();
-            }
-            
-            if let Event::WindowEvent { event: WindowEvent::Resized(mut size), .. } = event {
-                if size.width == 0xFFFFFFFF {
-                    size = self.window.inner_size();
+            let Event::WindowEvent { event, .. } = event else {
+                if let Event::RedrawRequested(_) = event {
+                    if let Some(console) = &mut console {
+                        console.poll(machine.interpreter_mut());
+                    }
+                    if !machine.run_frame() {
+                        exit(&mut machine, &mut session, control_flow);
+                        return;
+                    }
+                    machine.display_mut().window.request_redraw();
                 }
-                self.surface
-                    .resize_surface(size.width, size.height)
-                    .expect("Could not resize surface");
-                
-            }
-
-            if let Event::WindowEvent { event: WindowEvent::DroppedFile(path), .. } = &event {
-                func(GameEvents::DroppedFile(path.clone()), self.surface.frame_mut());
-            }
-            if let Event::WindowEvent { ref event, .. } = event {
-                if let WindowEvent::KeyboardInput { input, .. } = event {
-                    if let Some(key) = input.virtual_keycode {
-                        if let Some(position) = CONTROLS.iter().position(|k| k == &key) {
-                            let state = match input.state {
-                                ElementState::Pressed => true,
-                                ElementState::Released => false,
-                            };
-                            func(GameEvents::KeyInput(position, state), self.surface.frame_mut());
-                        } else {
-                            // BEL character usually makes a boop sound
-                            print!("\x07");
-                            std::io::stdout().flush().unwrap();
+                return;
+            };
+            match event {
+                WindowEvent::CloseRequested => exit(&mut machine, &mut session, control_flow),
+                WindowEvent::Resized(mut size) => {
+                    if size.width == 0xFFFFFFFF {
+                        size = machine.display_mut().window.inner_size();
+                    }
+                    machine.display_mut().resize(size);
+                }
+                WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                    machine.display_mut().resize(*new_inner_size);
+                }
+                WindowEvent::Focused(focused) => {
+                    machine.input_mut().push_back(InputEvent::Focus(focused));
+                }
+                WindowEvent::DroppedFile(path) => {
+                    if let Err(e) = load_file(&mut machine, &mut session, &path) {
+                        machine.osd_mut().toast(e);
+                    }
+                }
+                WindowEvent::KeyboardInput { input, .. } => {
+                    let Some(key) = input.virtual_keycode else {
+                        return;
+                    };
+                    let pressed = input.state == ElementState::Pressed;
+                    if machine.display_mut().menu.is_some() {
+                        if pressed {
+                            menu_key(&mut machine, &mut session, key);
+                        }
+                        return;
+                    }
+                    if pressed && key == VKC::Escape {
+                        let (interpreter, display) = machine.interpreter_and_display_mut();
+                        display.open_menu(interpreter, rom_dir.clone());
+                        return;
+                    }
+                    let keymap = session.settings().keymap;
+                    if let Some(position) = key_char(key).and_then(|c| keymap.key(c)) {
+                        machine.input_mut().push_back(InputEvent::Key(position, pressed));
+                    } else if let Some(command) = command(key, pressed) {
+                        machine.input_mut().push_back(InputEvent::Command(command));
+                    } else if pressed {
+                        match machine.display_mut().handle_hotkey(key) {
+                            Some(message) => machine.osd_mut().toast(message),
+                            None => {
+                                // BEL character usually makes a boop sound
+                                print!("\x07");
+                                std::io::stdout().flush().unwrap();
+                            }
                         }
                     }
                 }
+                _ => {}
             }
+        });
+    }
+}
 
-            if let &Event::RedrawRequested(_) = &event {
-                let beep = func(GameEvents::Redraw, self.surface.frame_mut());
-
-                let time_now = Instant::now();
+fn exit(machine: &mut WindowMachine, session: &mut Session, control_flow: &mut ControlFlow) {
+    let mut tracked = machine.display_mut().options.entries();
+    tracked.push(("readout", machine.osd().readout().to_string()));
+    if let Err(e) = session.save(machine.interpreter(), tracked) {
+        println!("Could not save settings: {e}");
+    }
+    control_flow.set_exit();
+}
 
-                let frame_time = time_now.duration_since(last_draw);
+/// Reads a ROM file and starts it with its settings, closing the menu
+fn load_file(machine: &mut WindowMachine, session: &mut Session, path: &Path) -> Result<(), String> {
+    let rom = fs::read(path).map_err(|e| format!("Could not load ROM: {e}"))?;
+    machine.handle(InputEvent::Load(rom));
+    session.load(machine.interpreter_mut());
+    set_volume(machine.audio_mut(), session.settings().volume);
+    set_title(&machine.display_mut().window, session);
+    if let Some(title) = session.title() {
+        machine.osd_mut().toast(title);
+    }
+    let (interpreter, display) = machine.interpreter_and_display_mut();
+    display.close_menu(interpreter);
+    if let Err(e) = add_recent(path.to_path_buf()) {
+        println!("Could not save recent files: {e}");
+    }
+    Ok(())
+}
 
-                let sleep = Duration::from_secs_f32(target_ms / 1000.0).saturating_sub(frame_time);
-                last_draw = time_now;
+/// Navigates the open menu, escape goes back to the game
+fn menu_key(machine: &mut WindowMachine, session: &mut Session, key: VKC) {
+    let menu_key = match key {
+        VKC::Up => MenuKey::Up,
+        VKC::Down => MenuKey::Down,
+        VKC::PageUp => MenuKey::PageUp,
+        VKC::PageDown => MenuKey::PageDown,
+        VKC::Home => MenuKey::Home,
+        VKC::End => MenuKey::End,
+        VKC::Return | VKC::NumpadEnter => MenuKey::Select,
+        VKC::Escape => {
+            let (interpreter, display) = machine.interpreter_and_display_mut();
+            display.close_menu(interpreter);
+            return;
+        }
+        _ => {
+            if let Some(message) = machine.display_mut().handle_hotkey(key) {
+                machine.osd_mut().toast(message);
+            }
+            return;
+        }
+    };
+    let Some(path) = machine.display_mut().menu.as_mut().and_then(|menu| menu.handle(menu_key)) else {
+        return;
+    };
+    if let Err(e) = load_file(machine, session, &path) {
+        if let Some(menu) = &mut machine.display_mut().menu {
+            menu.set_error(e);
+        }
+    }
+}
 
-                std::thread::sleep(sleep);
+/// Shows the title and authors of a ROM found in the database
+fn set_title(window: &Window, session: &Session) {
+    match session.title() {
+        Some(title) => window.set_title(&format!("{title} - {TITLE}")),
+        None => window.set_title(TITLE),
+    }
+}
 
-                if beep {
-                    let source = rodio::source::SineWave::new(400);
-                    rodio::play_raw(
-                        &self.audio,
-                        source.take_duration(Duration::from_secs_f32(target_ms / 1000.0)),
-                    );
-                }
+#[cfg(feature = "audio")]
+fn set_volume(audio: &mut WindowAudio, volume: u8) {
+    if let Some(beeper) = audio {
+        beeper.set_volume(volume);
+    }
+}
 
-                if let Err(e) = self.surface.render() {
-                    println!("{e}");
-                    control_flow.set_exit();
-                }
-                self.window.request_redraw();
-            }
+#[cfg(not(feature = "audio"))]
+fn set_volume(_: &mut WindowAudio, _: u8) {}
 
-        });
+/// Character a key types, used to look keys up in a `Keymap`
+fn key_char(key: VKC) -> Option<char> {
+    const LETTERS: [VKC; 26] = [
+        VKC::A, VKC::B, VKC::C, VKC::D, VKC::E, VKC::F, VKC::G, VKC::H, VKC::I,
+        VKC::J, VKC::K, VKC::L, VKC::M, VKC::N, VKC::O, VKC::P, VKC::Q, VKC::R,
+        VKC::S, VKC::T, VKC::U, VKC::V, VKC::W, VKC::X, VKC::Y, VKC::Z,
+    ];
+    const DIGITS: [VKC; 10] = [
+        VKC::Key0, VKC::Key1, VKC::Key2, VKC::Key3, VKC::Key4,
+        VKC::Key5, VKC::Key6, VKC::Key7, VKC::Key8, VKC::Key9,
+    ];
+    if let Some(i) = LETTERS.iter().position(|k| *k == key) {
+        return Some((b'a' + i as u8) as char);
+    }
+    if let Some(i) = DIGITS.iter().position(|k| *k == key) {
+        return Some((b'0' + i as u8) as char);
+    }
+    match key {
+        VKC::Comma => Some(','),
+        VKC::Period => Some('.'),
+        VKC::Slash => Some('/'),
+        VKC::Semicolon => Some(';'),
+        VKC::Apostrophe => Some('\''),
+        VKC::LBracket => Some('['),
+        VKC::RBracket => Some(']'),
+        _ => None,
     }
 }
-pub const MINUS_POSITION: usize = 16;
-pub const PLUS_POSITION: usize = 17;
 
-// Hexademical keyboard remapped to qwerty
-const CONTROLS: [VKC; 18] = [
-    VKC::Key1, VKC::Key2, VKC::Key3, VKC::Key4,
-    VKC::Q,    VKC::W,    VKC::E,    VKC::R,
-    VKC::A,    VKC::S,    VKC::D,    VKC::F,
-    VKC::Z,    VKC::X,    VKC::C,    VKC::V,
-    VKC::Minus, VKC::Equals, // special
-];
-
-pub enum GameEvents {
-    DroppedFile(PathBuf),
-    KeyInput(usize, bool),
-    Redraw,
-}
\ No newline at end of file
+/// Emulator controls, fast forward lasts while the key is held and the rest act on press
+fn command(key: VKC, pressed: bool) -> Option<Command> {
+    match key {
+        VKC::Space => return Some(Command::FastForward(pressed)),
+        _ if !pressed => return None,
+        _ => {}
+    }
+    Some(match key {
+        VKC::Minus => Command::SpeedDown,
+        VKC::Equals => Command::SpeedUp,
+        VKC::Tab => Command::CycleDisplayMode,
+        VKC::P => Command::TogglePause,
+        VKC::Back => Command::Reset,
+        VKC::N => Command::FrameAdvance,
+        VKC::M => Command::ToggleSlowMotion,
+        VKC::I => Command::ToggleReadout,
+        _ => return None,
+    })
+}
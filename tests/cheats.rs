@@ -0,0 +1,83 @@
+//! Freezes, patches and RAM search on a ROM that counts a byte at 300 down
+
+use chip8::cheat::{CheatFile, Comparison, RamSearch, Target};
+use chip8::chip8::Chip8Interpreter;
+use chip8::quirks::Quirks;
+
+/// I = 300, V0 = [300], V0 -= 1, [300] = V0, loop
+const ROM: [u8; 10] = [0xA3, 0x00, 0xF0, 0x65, 0x70, 0xFF, 0xF0, 0x55, 0x12, 0x00];
+
+fn interpreter() -> Chip8Interpreter {
+    Chip8Interpreter::builder().with_quirks(Quirks::SCHIP).with_rom(&ROM).with_memory(0x300, &[50]).build()
+}
+
+#[test]
+fn cheat_file_by_hash() {
+    let mut interpreter = interpreter();
+    let hash = interpreter.rom_hash().unwrap().to_ascii_uppercase();
+    let text = format!("freeze VE 7\n[0000]\nfreeze VD 1\n[{hash}]\nfreeze 300 09 # lives\npatch 20A 00 E0\n");
+    interpreter.set_cheat_file(Some(CheatFile::parse(&text).unwrap()));
+    assert_eq!(interpreter.cheats().lines(), ["freeze VE 07", "freeze 300 09", "patch 20A 00 E0"]);
+    assert_eq!(interpreter.read_u16(0x20A), 0x00E0);
+
+    interpreter.step_frame();
+    assert_eq!(interpreter.read_u8(0x300), 9);
+    assert_eq!(interpreter.registers()[0xE], 7);
+    assert_eq!(interpreter.registers()[0xD], 0);
+
+    interpreter.reset();
+    assert_eq!(interpreter.read_u16(0x20A), 0x00E0);
+    interpreter.load_bytes(&[0x12, 0x00]);
+    assert_eq!(interpreter.cheats().lines(), ["freeze VE 07"]);
+}
+
+#[test]
+fn bad_cheat_lines() {
+    assert!(CheatFile::parse("freeze 300").unwrap_err().starts_with("line 1:"));
+    assert!(CheatFile::parse("\nfreeze VG 1").unwrap_err().starts_with("line 2:"));
+    assert!(CheatFile::parse("patch V1 00").is_err());
+    assert_eq!(Target::parse("v3"), Ok(Target::Register(3)));
+    assert!(Target::parse("1000").is_err());
+}
+
+#[test]
+fn unfreeze() {
+    let mut interpreter = interpreter();
+    interpreter.freeze(Target::Memory(0x300), 50);
+    interpreter.step_frame();
+    assert_eq!(interpreter.read_u8(0x300), 50);
+    assert!(interpreter.unfreeze(Target::Memory(0x300)));
+    assert!(!interpreter.unfreeze(Target::Memory(0x300)));
+    interpreter.step_frame();
+    assert!(interpreter.read_u8(0x300) < 50);
+}
+
+#[test]
+fn cheats_leave_the_timers_alone() {
+    let mut interpreter = interpreter();
+    interpreter.set_delay_timer(30);
+    interpreter.freeze(Target::Memory(0x300), 50);
+    interpreter.set_cheat_file(Some(CheatFile::parse("patch 20A 00 E0").unwrap()));
+    assert_eq!(interpreter.delay_timer(), 30);
+}
+
+#[test]
+fn ram_search_finds_the_counter() {
+    let mut interpreter = interpreter();
+    let mut search = RamSearch::new(interpreter.memory());
+    for _ in 0..2 {
+        interpreter.step_frame();
+        search.narrow(interpreter.memory(), Comparison::Decreased);
+    }
+    assert_eq!(search.candidates(), [0x300]);
+
+    let mut search = RamSearch::new(interpreter.memory());
+    search.narrow(interpreter.memory(), Comparison::Unchanged);
+    assert_eq!(search.candidates().len(), 4096);
+    let value = interpreter.read_u8(0x300);
+    search.narrow(interpreter.memory(), Comparison::Equal(value));
+    assert!(search.candidates().contains(&0x300));
+    interpreter.step_frame();
+    search.narrow(interpreter.memory(), Comparison::Changed);
+    assert_eq!(search.candidates(), [0x300]);
+}
@@ -0,0 +1,33 @@
+//! Checking ROMs under every preset, and naming what the report links to
+#![cfg(feature = "std")]
+
+use std::{fs, path::PathBuf};
+
+use chip8::compat::{check, check_all, screenshot_name, Outcome, Report};
+use chip8::quirks::Quirks;
+
+fn report(rom: &str) -> Report {
+    check(&PathBuf::from(rom), &[0x12, 0x00], "modern", 1)
+}
+
+#[test]
+fn screenshot_names_do_not_collide() {
+    let names: Vec<String> = ["a b.ch8", "a_b.ch8", "dir/a b.ch8"].into_iter().map(|rom| screenshot_name(&report(rom))).collect();
+    assert!(names[0].starts_with("a_b_ch8-") && names[0].ends_with("-modern.png"), "{}", names[0]);
+    assert_ne!(names[0], names[1]);
+    assert_ne!(names[0], names[2]);
+    assert_eq!(screenshot_name(&report("a b.ch8")), names[0]);
+}
+
+#[test]
+fn check_all_runs_every_preset_in_order() {
+    let dir = std::env::temp_dir().join(format!("chip8-compat-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let rom = dir.join("loop.ch8");
+    fs::write(&rom, [0x12, 0x00]).unwrap();
+    let reports = check_all(&[rom, dir.join("missing.ch8")], 2);
+    let presets: Vec<&str> = reports.iter().map(|report| report.preset).collect();
+    assert_eq!(presets, Quirks::PRESETS);
+    assert!(reports.iter().all(|report| report.outcome == Outcome::Hang && report.frames == 2));
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,64 @@
+//! Coverage recorded from the accesses single instructions make
+
+use chip8::chip8::{Chip8Interpreter, Chip8InterpreterBuilder};
+use chip8::coverage::{Access, Coverage};
+use chip8::quirks::Quirks;
+
+/// Runs one instruction at the builder's program counter with coverage recording
+fn run(builder: Chip8InterpreterBuilder, opcode: u16) -> Chip8Interpreter {
+    let mut interpreter = builder.build();
+    interpreter.set_coverage(Some(Coverage::new()));
+    let pc = interpreter.program_counter();
+    interpreter.write_u16(pc, opcode);
+    interpreter.execute_cycle();
+    interpreter
+}
+
+fn accesses(interpreter: &Chip8Interpreter, addresses: &[u16]) -> Vec<Access> {
+    addresses.iter().map(|address| interpreter.coverage().unwrap().access(*address)).collect()
+}
+
+fn read(count: u32) -> Access {
+    Access { read: count, ..Access::default() }
+}
+
+fn written(count: u32) -> Access {
+    Access { written: count, ..Access::default() }
+}
+
+fn sprite(count: u32) -> Access {
+    Access { sprite: count, ..Access::default() }
+}
+
+#[test]
+fn fetch_marks_both_bytes() {
+    let interpreter = run(Chip8Interpreter::builder().with_program_counter(0xFFF), 0x6000);
+    let executed = Access { executed: 1, ..Access::default() };
+    assert_eq!(accesses(&interpreter, &[0xFFF, 0x000]), [executed, executed]);
+}
+
+#[test]
+fn reads_and_writes_wrap_around_memory() {
+    let interpreter = run(Chip8Interpreter::builder().with_index(0xFFE), 0xF265);
+    assert_eq!(accesses(&interpreter, &[0xFFD, 0xFFE, 0xFFF, 0x000]), [Access::default(), read(1), read(1), read(1)]);
+    let interpreter = run(Chip8Interpreter::builder().with_index(0xFFF).with_register(0, 123), 0xF033);
+    assert_eq!(accesses(&interpreter, &[0xFFF, 0x000, 0x001]), [written(1), Access::default(), Access::default()]);
+}
+
+#[test]
+fn protected_font_is_not_written() {
+    let interpreter = run(Chip8Interpreter::builder().with_index(0x04E), 0xF355);
+    assert_eq!(accesses(&interpreter, &[0x04E, 0x04F, 0x050, 0x051]), [Access::default(), Access::default(), written(1), written(1)]);
+    let quirks = Quirks { writes_font: true, ..Quirks::default() };
+    let interpreter = run(Chip8Interpreter::builder().with_quirks(quirks).with_index(0x04E), 0xF355);
+    assert_eq!(accesses(&interpreter, &[0x04E, 0x04F]), [written(1), written(1)]);
+}
+
+#[test]
+fn clipped_sprite_rows_are_not_drawn() {
+    let builder = Chip8Interpreter::builder().with_index(0x300).with_register(1, 30);
+    let clipped = run(builder.clone().with_quirks(Quirks { clip_sprites: true, ..Quirks::default() }), 0xD014);
+    assert_eq!(accesses(&clipped, &[0x300, 0x301, 0x302, 0x303]), [sprite(1), sprite(1), Access::default(), Access::default()]);
+    let wrapped = run(builder.with_quirks(Quirks { clip_sprites: false, ..Quirks::default() }), 0xD014);
+    assert_eq!(accesses(&wrapped, &[0x300, 0x301, 0x302, 0x303]), [sprite(1); 4]);
+}
@@ -0,0 +1,181 @@
+//! A scripted client talking to the debug adapter over an in-memory stream
+#![cfg(feature = "dap")]
+
+use std::{
+    fs,
+    io::{self, BufRead, BufReader, Read, Write},
+    path::PathBuf,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+    time::Duration,
+};
+
+use chip8::dap::Server;
+use serde_json::{json, Value};
+
+/// Long enough for anything the adapter does, short enough that a hang fails the test
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Bytes written to the other end's `PipeWriter` come out here
+struct PipeReader {
+    receiver: Receiver<Vec<u8>>,
+    buffer: Vec<u8>,
+}
+
+impl Read for PipeReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.buffer.is_empty() {
+            match self.receiver.recv_timeout(TIMEOUT) {
+                Ok(bytes) => self.buffer = bytes,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(0),
+                Err(e) => return Err(io::Error::new(io::ErrorKind::TimedOut, e)),
+            }
+        }
+        let length = out.len().min(self.buffer.len());
+        out[..length].copy_from_slice(&self.buffer[..length]);
+        self.buffer.drain(..length);
+        Ok(length)
+    }
+}
+
+struct PipeWriter(Sender<Vec<u8>>);
+
+impl Write for PipeWriter {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.send(bytes.to_vec()).map_err(|e| io::Error::new(io::ErrorKind::BrokenPipe, e))?;
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn pipe() -> (PipeWriter, PipeReader) {
+    let (sender, receiver) = mpsc::channel();
+    (PipeWriter(sender), PipeReader { receiver, buffer: Vec::new() })
+}
+
+struct Client {
+    to_server: PipeWriter,
+    from_server: BufReader<PipeReader>,
+    seq: u64,
+    // Events that arrived while waiting for a response
+    events: Vec<Value>,
+}
+
+impl Client {
+    fn send(&mut self, command: &str, arguments: Value) -> u64 {
+        self.seq += 1;
+        let body = json!({ "seq": self.seq, "type": "request", "command": command, "arguments": arguments }).to_string();
+        write!(self.to_server, "Content-Length: {}\r\n\r\n{body}", body.len()).unwrap();
+        self.seq
+    }
+
+    fn receive(&mut self) -> Value {
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            self.from_server.read_line(&mut header).unwrap();
+            match header.trim().split_once(':') {
+                Some((_, value)) => length = value.trim().parse().unwrap(),
+                None if header.trim().is_empty() => break,
+                None => panic!("unexpected header {header:?}"),
+            }
+        }
+        let mut body = vec![0; length];
+        self.from_server.read_exact(&mut body).unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+    /// Sends a request and returns its response body, which has to be successful
+    fn request(&mut self, command: &str, arguments: Value) -> Value {
+        let seq = self.send(command, arguments);
+        loop {
+            let message = self.receive();
+            if message["type"] == "event" {
+                self.events.push(message);
+            } else if message["request_seq"] == seq {
+                assert_eq!(message["success"], true, "{message}");
+                return message["body"].clone();
+            }
+        }
+    }
+    /// Body of the next event with this name, including ones that already arrived
+    fn event(&mut self, name: &str) -> Value {
+        if let Some(at) = self.events.iter().position(|event| event["event"] == name) {
+            return self.events.remove(at)["body"].clone();
+        }
+        loop {
+            let message = self.receive();
+            if message["event"] == name {
+                return message["body"].clone();
+            }
+            self.events.push(message);
+        }
+    }
+}
+
+/// Writes a ROM with a symbol map and a source file next to it, in a directory of its own
+fn program() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("chip8-dap-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let rom = [
+        0x60, 0x01, // 200 V0 = 1
+        0x22, 0x06, // 202 call 206
+        0x12, 0x04, // 204 loop
+        0x70, 0x01, // 206 V0 += 1
+        0x00, 0xEE, // 208 return
+    ];
+    fs::write(dir.join("game.ch8"), rom).unwrap();
+    fs::write(dir.join("game.asm"), "v0 := 1\nsub\nloop again\n: sub\nv0 += 1\nreturn\n").unwrap();
+    fs::write(dir.join("game.sym"), "200 game.asm:1\n202 game.asm:2\n204 game.asm:3\n206 game.asm:5\n208 game.asm:6\n").unwrap();
+    dir
+}
+
+#[test]
+fn breakpoint_and_stack_trace() {
+    let dir = program();
+    let (server_out, client_in) = pipe();
+    let (client_out, server_in) = pipe();
+    let server = thread::spawn(move || Server::new(Box::new(server_out)).run(server_in));
+    let mut client = Client { to_server: client_out, from_server: BufReader::new(client_in), seq: 0, events: Vec::new() };
+
+    let capabilities = client.request("initialize", json!({ "adapterID": "chip8" }));
+    assert_eq!(capabilities["supportsConfigurationDoneRequest"], true);
+    let program = dir.join("game.ch8");
+    client.request("launch", json!({ "program": program, "quirks": "modern", "speed": 600, "stopOnEntry": true }));
+    client.event("initialized");
+
+    // Line 4 is a label, the breakpoint moves to the instruction on line 5
+    let source = json!({ "path": dir.join("game.asm") });
+    let breakpoints = client.request("setBreakpoints", json!({ "source": source, "breakpoints": [{ "line": 4 }] }));
+    assert_eq!(breakpoints["breakpoints"][0]["verified"], true);
+    assert_eq!(breakpoints["breakpoints"][0]["line"], 5);
+    assert_eq!(breakpoints["breakpoints"][0]["instructionReference"], "0x206");
+    client.request("configurationDone", Value::Null);
+    assert_eq!(client.event("stopped")["reason"], "entry");
+
+    client.request("continue", json!({ "threadId": 1 }));
+    assert_eq!(client.event("stopped")["reason"], "breakpoint");
+    let trace = client.request("stackTrace", json!({ "threadId": 1 }));
+    let frames = trace["stackFrames"].as_array().unwrap();
+    assert_eq!(frames.len(), 2);
+    assert_eq!(frames[0]["instructionPointerReference"], "0x206");
+    assert_eq!(frames[0]["line"], 5);
+    assert_eq!(frames[0]["source"]["name"], "game.asm");
+    // The call the subroutine was entered from
+    assert_eq!(frames[1]["instructionPointerReference"], "0x202");
+    assert_eq!(frames[1]["line"], 2);
+
+    let memory = client.request("readMemory", json!({ "memoryReference": "0x200", "count": 2 }));
+    assert_eq!(memory["data"], "YAE=");
+    // Numbers a client can send but that are nowhere near memory read nothing
+    let memory = client.request("readMemory", json!({ "memoryReference": "0x200", "offset": i64::MAX, "count": i64::MAX }));
+    assert_eq!(memory["data"], "");
+    let memory = client.request("readMemory", json!({ "memoryReference": "0x200", "offset": i64::MIN, "count": 2 }));
+    assert_eq!(memory["address"], "0x000");
+
+    client.request("disconnect", Value::Null);
+    server.join().unwrap().unwrap();
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,111 @@
+//! Reading chip-8-database entries and applying them to settings
+#![cfg(feature = "database")]
+
+use chip8::chip8::{Chip8Interpreter, Palette, MAX_SPEED, MIN_SPEED};
+use chip8::database::{Database, RomInfo};
+use chip8::host::Keymap;
+use chip8::quirks::Quirks;
+use chip8::settings::{Session, Settings, SettingsFile};
+
+const PROGRAMS: &str = r##"[
+    {
+        "title": "Pong",
+        "authors": ["Paul Vervalin"],
+        "roms": {
+            "ABCDEF0123": {
+                "platforms": ["originalChip8", "modernChip8"],
+                "quirkyPlatforms": { "originalChip8": { "logic": false, "wrap": true } },
+                "tickrate": 15,
+                "keys": { "up": 1, "down": 4, "player2Up": 12 },
+                "colors": { "pixels": ["#000000", "ffb000"] }
+            }
+        }
+    },
+    { "title": "No authors", "roms": { "1234": { "platforms": ["someFuturePlatform"] } } },
+    { "title": "No roms" }
+]"##;
+
+fn info(keys: &[(&str, usize)]) -> RomInfo {
+    RomInfo { keys: keys.iter().map(|(name, key)| (name.to_string(), *key)).collect(), ..RomInfo::default() }
+}
+
+fn keymap(info: &RomInfo) -> String {
+    let mut settings = Settings::default();
+    info.apply(&mut settings);
+    settings.keymap.to_string()
+}
+
+#[test]
+fn parse_reads_every_rom() {
+    let database = Database::parse(PROGRAMS).unwrap();
+    assert_eq!(database.len(), 2);
+    let pong = database.get("abcdef0123").unwrap();
+    assert_eq!(pong.name(), "Pong by Paul Vervalin");
+    assert_eq!(pong.platform.as_deref(), Some("originalChip8"));
+    assert_eq!(pong.quirks, Some(Quirks { logic_resets_vf: false, clip_sprites: false, ..Quirks::VIP }));
+    assert_eq!(pong.tickrate, Some(15));
+    assert_eq!(pong.palette, Some(Palette { on: [0xFF, 0xB0, 0], off: [0, 0, 0] }));
+    let unknown = database.get("1234").unwrap();
+    assert_eq!(unknown.name(), "No authors");
+    assert_eq!(unknown.quirks, None);
+    assert!(Database::parse("{}").is_err());
+}
+
+#[test]
+fn apply_returns_changed_keys() {
+    let database = Database::parse(PROGRAMS).unwrap();
+    let mut settings = Settings::default();
+    let changed = database.get("ABCDEF0123").unwrap().apply(&mut settings);
+    assert_eq!(changed, ["quirks", "speed", "palette", "keymap"]);
+    assert_eq!(settings.speed, 15);
+    assert_eq!(settings.palette, Palette { on: [0xFF, 0xB0, 0], off: [0, 0, 0] });
+    // Up moves from 2 to W and down from Q to S, the keys that were there take 2 and Q
+    assert_eq!(settings.keymap.to_string(), "1w34s2eraqdfzxcv");
+}
+
+#[test]
+fn tickrate_is_clamped() {
+    let speed = |tickrate| {
+        let mut settings = Settings::default();
+        RomInfo { tickrate: Some(tickrate), ..RomInfo::default() }.apply(&mut settings);
+        settings.speed
+    };
+    assert_eq!(speed(0), MIN_SPEED);
+    assert_eq!(speed(u32::MAX), MAX_SPEED);
+    assert_eq!(speed(15), 15);
+}
+
+#[test]
+fn game_keys_move_onto_wasd() {
+    assert_eq!(keymap(&info(&[("up", 2), ("left", 4), ("down", 8), ("right", 6)])), "12w4a3drsqefzxcv");
+    // Already where they belong
+    assert_eq!(keymap(&info(&[("up", 5), ("left", 8)])), Keymap::LINEAR.to_string());
+}
+
+#[test]
+fn game_keys_on_each_others_characters() {
+    // Up is on A's key and left on W's
+    assert_eq!(keymap(&info(&[("up", 8), ("left", 5)])), "1234qaerwsdfzxcv");
+}
+
+#[test]
+fn hex_key_with_two_names_keeps_the_first() {
+    assert_eq!(keymap(&info(&[("up", 7), ("left", 7)])), "1234qrewasdfzxcv");
+}
+
+#[test]
+fn database_needs_a_settings_file() {
+    let mut interpreter = Chip8Interpreter::builder().with_rom(&[0x12, 0x00]).build();
+    let hash = interpreter.rom_hash().unwrap().to_string();
+    let dir = std::env::temp_dir().join(format!("chip8-database-test-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("chip8")).unwrap();
+    let programs = format!(r#"[{{ "title": "Loop", "roms": {{ "{hash}": {{ "tickrate": 20 }} }} }}]"#);
+    std::fs::write(dir.join("chip8").join("programs.json"), programs).unwrap();
+    std::env::set_var("XDG_CONFIG_HOME", &dir);
+
+    let session = Session::new(SettingsFile::default(), Vec::new(), &mut interpreter);
+    assert!(session.rom_info().is_none());
+    let session = Session::new(SettingsFile::open(dir.join("chip8").join("settings.cfg")), Vec::new(), &mut interpreter);
+    assert_eq!(session.rom_info().map(|info| info.title.as_str()), Some("Loop"));
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,90 @@
+//! Display modes checked on the RGBA buffer `draw_pixels` fills, no window needed
+
+use chip8::chip8::{Chip8Interpreter, DisplayMode, Palette, HEIGHT, WIDTH};
+
+/// Top left pixel lit by a one row sprite, drawing it again turns it off
+const DRAW: u16 = 0xD001;
+const CLEAR: u16 = 0x00E0;
+
+fn machine(mode: DisplayMode) -> Chip8Interpreter {
+    let mut interpreter = Chip8Interpreter::builder().with_index(0x300).with_memory(0x300, &[0x80]).build();
+    interpreter.set_display_mode(mode);
+    interpreter
+}
+
+/// Runs an instruction in place, without moving on to whatever follows it in memory
+fn run(interpreter: &mut Chip8Interpreter, opcode: u16) {
+    interpreter.write_u16(0x200, opcode);
+    interpreter.set_program_counter(0x200);
+    interpreter.execute_cycle();
+}
+
+/// Presents a frame and returns the colour of the top left pixel
+fn present(interpreter: &mut Chip8Interpreter) -> [u8; 4] {
+    let mut pixels = vec![0; WIDTH * HEIGHT * 4];
+    interpreter.draw_pixels(&mut pixels);
+    pixels[..4].try_into().unwrap()
+}
+
+#[test]
+fn raw_shows_vram() {
+    let mut interpreter = machine(DisplayMode::Raw);
+    run(&mut interpreter, DRAW);
+    assert_eq!(present(&mut interpreter), [255, 255, 255, 255]);
+    run(&mut interpreter, CLEAR);
+    assert_eq!(present(&mut interpreter), [0, 0, 0, 255]);
+}
+
+#[test]
+fn persistence_fades_out() {
+    let mut interpreter = machine(DisplayMode::Persistence(4));
+    run(&mut interpreter, DRAW);
+    assert_eq!(present(&mut interpreter), [255, 255, 255, 255]);
+    run(&mut interpreter, CLEAR);
+    let levels: Vec<u8> = (0..5).map(|_| present(&mut interpreter)[0]).collect();
+    assert_eq!(levels, [191, 127, 63, 0, 0]);
+    // Lit again at full brightness, the fade starts over
+    run(&mut interpreter, DRAW);
+    assert_eq!(present(&mut interpreter), [255, 255, 255, 255]);
+    run(&mut interpreter, CLEAR);
+    assert_eq!(present(&mut interpreter)[0], 191);
+}
+
+#[test]
+fn vblank_keeps_pixels_lit_since_last_present() {
+    let mut interpreter = machine(DisplayMode::Vblank);
+    // Drawn and erased between two presents, like a flickering sprite
+    run(&mut interpreter, DRAW);
+    run(&mut interpreter, DRAW);
+    assert!(!interpreter.vram()[0]);
+    assert_eq!(present(&mut interpreter), [255, 255, 255, 255]);
+    // Nothing was lit since that present
+    assert_eq!(present(&mut interpreter), [0, 0, 0, 255]);
+}
+
+#[test]
+fn palette_colours_pixels() {
+    let mut interpreter = machine(DisplayMode::Persistence(4));
+    interpreter.set_palette(Palette::LCD);
+    run(&mut interpreter, DRAW);
+    assert_eq!(present(&mut interpreter), [15, 56, 15, 255]);
+    run(&mut interpreter, CLEAR);
+    // A quarter of the way back to the unlit colour
+    assert_eq!(present(&mut interpreter), [51, 90, 15, 255]);
+    for _ in 0..3 {
+        present(&mut interpreter);
+    }
+    assert_eq!(present(&mut interpreter), [155, 188, 15, 255]);
+}
+
+#[test]
+fn palette_names_and_colours() {
+    for name in Palette::PRESETS {
+        assert_eq!(Palette::parse(name).map(|palette| palette.to_string()).as_deref(), Some(name));
+    }
+    let custom = Palette::parse("#102030,405060").unwrap();
+    assert_eq!(custom, Palette { on: [0x10, 0x20, 0x30], off: [0x40, 0x50, 0x60] });
+    assert_eq!(custom.to_string(), "102030,405060");
+    assert_eq!(Palette::parse("102030"), None);
+    assert_eq!(Palette::parse("10203,405060"), None);
+}
@@ -0,0 +1,83 @@
+//! Key events queued from frontends and how the ROM sees them
+
+use chip8::chip8::{Chip8Interpreter, KeyEvent};
+
+/// Jumps to itself forever
+const LOOP: [u8; 2] = [0x12, 0x00];
+
+#[test]
+fn keys_past_f_are_ignored() {
+    let mut interpreter = Chip8Interpreter::builder().with_rom(&LOOP).build();
+    // Would be key 0 once cut down to a byte
+    interpreter.queue_key(0x100, true);
+    interpreter.queue_key(0x10, true);
+    interpreter.execute_cycle();
+    assert_eq!(interpreter.keyboard(), [false; 16]);
+    interpreter.update_key(0x10, true);
+    assert_eq!(interpreter.keyboard(), [false; 16]);
+}
+
+#[test]
+fn quick_tap_is_seen() {
+    let rom = [
+        0x65, 0x05, // V5 = 5
+        0xE5, 0x9E, // skip if key 5 is down
+        0x12, 0x04, // missed it
+        0x12, 0x06, // saw it
+    ];
+    let mut interpreter = Chip8Interpreter::builder().with_rom(&rom).build();
+    // Let go before a single instruction has run
+    interpreter.queue_key(5, true);
+    interpreter.queue_key(5, false);
+    interpreter.step_frame();
+    assert_eq!(interpreter.program_counter(), 0x206);
+    assert!(!interpreter.keyboard()[5]);
+}
+
+#[test]
+fn keys_apply_with_nothing_running() {
+    let mut interpreter = Chip8Interpreter::new();
+    interpreter.queue_key(5, true);
+    interpreter.execute_cycle();
+    assert!(interpreter.keyboard()[5]);
+    // Never read, so the release waits for the frame after the press to end
+    interpreter.queue_key(5, false);
+    interpreter.step_frame();
+    interpreter.step_frame();
+    interpreter.execute_cycle();
+    assert!(!interpreter.keyboard()[5]);
+}
+
+/// Waits for two keys and adds them up, then spins
+const ADD_KEYS: [u8; 8] = [0xF0, 0x0A, 0xF1, 0x0A, 0x80, 0x14, 0x12, 0x06];
+
+#[test]
+fn recorded_keys_replay_the_same_way() {
+    let mut recording = Chip8Interpreter::builder().with_rom(&ADD_KEYS).build();
+    recording.record_keys(true);
+    // Keys pressed before Fx0A starts waiting do not count
+    recording.step_frame();
+    let mut frames = 1;
+    for (key, pressed) in [(3, true), (3, false), (7, true), (7, false)] {
+        recording.queue_key(key, pressed);
+        for _ in 0..3 {
+            recording.step_frame();
+            frames += 1;
+        }
+    }
+    assert_eq!(recording.registers()[0], 10);
+    let recorded: Vec<KeyEvent> = recording.recorded_keys().to_vec();
+    assert_eq!(recorded.len(), 4);
+
+    let mut replay = Chip8Interpreter::builder().with_rom(&ADD_KEYS).build();
+    replay.record_keys(true);
+    for line in recorded.iter().map(ToString::to_string) {
+        replay.queue_key_event(KeyEvent::parse(&line).unwrap());
+    }
+    for _ in 0..frames {
+        replay.step_frame();
+    }
+    assert_eq!(replay.registers(), recording.registers());
+    assert_eq!(replay.recorded_keys(), recorded);
+    assert_eq!(replay.instruction_count(), recording.instruction_count());
+}
@@ -0,0 +1,75 @@
+//! Events an observer hears while a short ROM runs
+
+use std::{cell::RefCell, rc::Rc};
+
+use chip8::chip8::Chip8Interpreter;
+use chip8::observer::Observer;
+
+#[derive(Default)]
+struct Log {
+    events: Vec<String>,
+}
+
+impl Observer for Log {
+    fn before_execute(&mut self, _: &Chip8Interpreter, pc: u16, opcode: u16) {
+        self.events.push(format!("run {pc:03X} {opcode:04X}"));
+    }
+
+    fn memory_read(&mut self, _: &Chip8Interpreter, address: u16, value: u8) {
+        self.events.push(format!("read {address:03X} {value}"));
+    }
+
+    fn memory_write(&mut self, machine: &Chip8Interpreter, address: u16, value: u8) {
+        assert_eq!(machine.read_u8(address), value);
+        self.events.push(format!("write {address:03X} {value}"));
+    }
+
+    fn sprite_draw(&mut self, _: &Chip8Interpreter, x: u8, y: u8, address: u16, rows: u8, collision: bool) {
+        self.events.push(format!("draw {x},{y} {address:03X} {rows} {collision}"));
+    }
+
+    fn sound(&mut self, _: &Chip8Interpreter, on: bool) {
+        self.events.push(format!("sound {on}"));
+    }
+}
+
+#[test]
+fn events_in_order() {
+    let rom = [
+        0x60, 0x07, // V0 = 7
+        0xA3, 0x00, // I = 300
+        0xF0, 0x33, // BCD of V0
+        0xF0, 0x65, // V0 = [300]
+        0xDF, 0xF1, // draw 1 row at VF, VF from I, now 301
+        0x61, 0x02, // V1 = 2
+        0xF1, 0x18, // sound for 2 frames
+    ];
+    let log = Rc::new(RefCell::new(Log::default()));
+    let mut interpreter = Chip8Interpreter::builder().with_rom(&rom).build();
+    interpreter.set_observer(Some(Box::new(log.clone())));
+    for _ in 0..rom.len() / 2 {
+        interpreter.execute_cycle();
+    }
+    interpreter.tick_timers();
+    interpreter.tick_timers();
+    assert!(interpreter.take_observer().is_some());
+    assert_eq!(
+        log.borrow().events,
+        [
+            "run 200 6007",
+            "run 202 A300",
+            "run 204 F033",
+            "write 300 0",
+            "write 301 0",
+            "write 302 7",
+            "run 206 F065",
+            "read 300 0",
+            "run 208 DFF1",
+            "draw 0,0 301 1 false",
+            "run 20A 6102",
+            "run 20C F118",
+            "sound true",
+            "sound false",
+        ]
+    );
+}
@@ -0,0 +1,336 @@
+//! Single instructions run from a machine set up with `Chip8InterpreterBuilder`
+
+use chip8::chip8::{Chip8Interpreter, Chip8InterpreterBuilder, DEFAULT_SPEED, FRAME_RATE};
+use chip8::quirks::Quirks;
+use chip8::timing::{vip_cycles, Timing, VIP_FRAME_BUDGET};
+
+/// Runs one instruction placed at the builder's program counter, 0x200 unless it was changed
+fn run(builder: Chip8InterpreterBuilder, opcode: u16) -> Chip8Interpreter {
+    let mut interpreter = builder.build();
+    let pc = interpreter.program_counter();
+    interpreter.write_u16(pc, opcode);
+    interpreter.execute_cycle();
+    interpreter
+}
+
+#[test]
+fn add_sets_carry() {
+    let interpreter = run(Chip8Interpreter::builder().with_register(0, 0xF0).with_register(1, 0x20), 0x8014);
+    assert_eq!(interpreter.registers()[0], 0x10);
+    assert_eq!(interpreter.registers()[0xF], 1);
+}
+
+#[test]
+fn return_addresses_are_masked() {
+    let mut ret = run(Chip8Interpreter::builder().with_stack(&[0xFFFF]), 0x00EE);
+    assert_eq!(ret.program_counter(), 0xFFF);
+    // Fetches from the last byte of memory, wrapping around instead of running off the end
+    ret.execute_cycle();
+}
+
+#[test]
+fn stack_keeps_the_first_16_addresses() {
+    let stack: Vec<u16> = (0..20).map(|n| 0x300 + n * 2).collect();
+    let mut interpreter = Chip8Interpreter::builder().with_stack(&stack).build();
+    assert_eq!(interpreter.stack(), &stack[..16]);
+    interpreter.set_stack(&[0x1202]);
+    assert_eq!(interpreter.stack(), [0x202]);
+}
+
+#[test]
+fn subtract_without_borrow_sets_vf() {
+    // Equal values do not borrow, so VF is 1 for both orders
+    let builder = Chip8Interpreter::builder().with_register(0, 5).with_register(1, 5);
+    let sub = run(builder.clone(), 0x8015);
+    assert_eq!(sub.registers()[0], 0);
+    assert_eq!(sub.registers()[0xF], 1);
+    let subn = run(builder, 0x8017);
+    assert_eq!(subn.registers()[0], 0);
+    assert_eq!(subn.registers()[0xF], 1);
+}
+
+#[test]
+fn random_reaches_ff() {
+    let builder = Chip8Interpreter::builder();
+    let seen_ff = (0..2048).any(|seed| run(builder.clone().with_seed(seed), 0xC0FF).registers()[0] == 0xFF);
+    assert!(seen_ff);
+}
+
+#[test]
+fn index_uses_all_twelve_bits() {
+    assert_eq!(run(Chip8Interpreter::builder(), 0xAFFF).index(), 0xFFF);
+}
+
+#[test]
+fn font_character_uses_the_low_digit() {
+    assert_eq!(run(Chip8Interpreter::builder().with_register(0, 0xFA), 0xF029).index(), 0xA * 5);
+}
+
+#[test]
+fn shift_quirk() {
+    let builder = Chip8Interpreter::builder().with_register(0, 0b100).with_register(1, 0b11);
+    let modern = run(builder.clone(), 0x8016);
+    assert_eq!(modern.registers()[0], 0b10);
+    assert_eq!(modern.registers()[0xF], 0);
+    let vip = run(builder.with_quirks(Quirks::VIP), 0x8016);
+    assert_eq!(vip.registers()[0], 0b1);
+    assert_eq!(vip.registers()[0xF], 1);
+}
+
+#[test]
+fn call_and_return() {
+    let call = run(Chip8Interpreter::builder(), 0x2300);
+    assert_eq!(call.program_counter(), 0x300);
+    assert_eq!(call.stack(), [0x202]);
+    let ret = run(Chip8Interpreter::builder().with_program_counter(0x300).with_stack(&[0x202]), 0x00EE);
+    assert_eq!(ret.program_counter(), 0x202);
+    assert!(ret.stack().is_empty());
+}
+
+#[test]
+fn skip_if_equal() {
+    let taken = run(Chip8Interpreter::builder().with_register(3, 0x42), 0x3342);
+    assert_eq!(taken.program_counter(), 0x204);
+    let not_taken = run(Chip8Interpreter::builder().with_register(3, 0x41), 0x3342);
+    assert_eq!(not_taken.program_counter(), 0x202);
+}
+
+#[test]
+fn bcd() {
+    let interpreter = run(Chip8Interpreter::builder().with_register(2, 254).with_index(0x300), 0xF233);
+    assert_eq!(interpreter.memory()[0x300..0x303], [2, 5, 4]);
+}
+
+#[test]
+fn load_increments_index() {
+    let builder = Chip8Interpreter::builder().with_index(0x300).with_memory(0x300, &[1, 2, 3]);
+    let modern = run(builder.clone(), 0xF265);
+    assert_eq!(modern.registers()[..3], [1, 2, 3]);
+    assert_eq!(modern.index(), 0x303);
+    let schip = run(builder.with_quirks(Quirks::SCHIP), 0xF265);
+    assert_eq!(schip.index(), 0x300);
+}
+
+#[test]
+fn timers() {
+    let interpreter = run(Chip8Interpreter::builder().with_delay_timer(30), 0xF507);
+    assert_eq!(interpreter.registers()[5], 30);
+    let interpreter = run(Chip8Interpreter::builder().with_register(5, 9), 0xF518);
+    assert_eq!(interpreter.sound_timer(), 9);
+    assert!(interpreter.should_beep());
+}
+
+#[test]
+fn sprite_collision() {
+    let builder = Chip8Interpreter::builder().with_index(0x300).with_memory(0x300, &[0x80]);
+    let mut interpreter = run(builder, 0xD001);
+    assert!(interpreter.vram()[0]);
+    assert_eq!(interpreter.registers()[0xF], 0);
+    interpreter.set_program_counter(0x200);
+    interpreter.execute_cycle();
+    assert!(!interpreter.vram()[0]);
+    assert_eq!(interpreter.registers()[0xF], 1);
+}
+
+#[test]
+fn clones_run_the_same() {
+    let mut a = Chip8Interpreter::builder().with_rom(&[0xC0, 0xFF, 0x12, 0x00]).with_seed(7).build();
+    a.execute_cycle();
+    let mut b = a.clone();
+    assert_eq!(a, b);
+    for _ in 0..10 {
+        a.execute_cycle();
+        b.execute_cycle();
+    }
+    assert_eq!(a, b);
+    b.set_register(0, b.registers()[0].wrapping_add(1));
+    assert_ne!(a, b);
+}
+
+#[test]
+fn speed_and_key_clock_are_compared() {
+    let a = Chip8Interpreter::builder().with_rom(&[0x12, 0x00]).build();
+    let mut b = a.clone();
+    b.set_speed(DEFAULT_SPEED + 1);
+    assert_ne!(a, b);
+    // Jumping to itself leaves everything but the count that key events are timed by as it was
+    let mut b = a.clone();
+    b.execute_cycle();
+    assert_ne!(a, b);
+}
+
+#[test]
+fn timers_tick_once_per_frame() {
+    let mut interpreter = Chip8Interpreter::builder().with_rom(&[0x12, 0x00]).build();
+    interpreter.set_delay_timer(30);
+    interpreter.set_sound_timer(5);
+    for _ in 0..100 {
+        interpreter.execute_cycle();
+    }
+    assert_eq!((interpreter.delay_timer(), interpreter.sound_timer()), (30, 5));
+    interpreter.step_frame();
+    assert_eq!((interpreter.delay_timer(), interpreter.sound_timer()), (29, 4));
+    // However many instructions a frame runs
+    interpreter.set_speed(200);
+    interpreter.step_frame();
+    assert_eq!((interpreter.delay_timer(), interpreter.sound_timer()), (28, 3));
+}
+
+#[test]
+fn set_delay_timer_is_exact() {
+    let interpreter = run(Chip8Interpreter::builder().with_register(5, 9), 0xF515);
+    assert_eq!(interpreter.delay_timer(), 9);
+    let interpreter = run(Chip8Interpreter::builder().with_register(5, 0xFF), 0xF515);
+    assert_eq!(interpreter.delay_timer(), 0xFF);
+}
+
+#[test]
+fn store_uses_index_and_includes_vx() {
+    // The low bits of F255 would be address 255 if they were used
+    let builder = Chip8Interpreter::builder().with_registers([1, 2, 3, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]).with_index(0x300);
+    let interpreter = run(builder, 0xF255);
+    assert_eq!(interpreter.memory()[0x300..0x304], [1, 2, 3, 0]);
+    assert_eq!(interpreter.memory()[0x255..0x258], [0, 0, 0]);
+    assert_eq!(interpreter.index(), 0x303);
+}
+
+#[test]
+fn load_uses_index_and_includes_vx() {
+    let builder = Chip8Interpreter::builder().with_index(0x300).with_memory(0x255, &[9, 9, 9]).with_memory(0x300, &[1, 2, 3, 4]);
+    let interpreter = run(builder, 0xF265);
+    assert_eq!(interpreter.registers()[..4], [1, 2, 3, 0]);
+}
+
+#[test]
+fn shift_left_quirk() {
+    let builder = Chip8Interpreter::builder().with_register(0, 0b1000_0001).with_register(1, 0b0100_0000);
+    let modern = run(builder.clone(), 0x801E);
+    assert_eq!(modern.registers()[0], 0b10);
+    assert_eq!(modern.registers()[0xF], 1);
+    let vip = run(builder.with_quirks(Quirks::VIP), 0x801E);
+    assert_eq!(vip.registers()[0], 0b1000_0000);
+    assert_eq!(vip.registers()[0xF], 0);
+}
+
+#[test]
+fn shift_flag_wins_over_vf_as_destination() {
+    let interpreter = run(Chip8Interpreter::builder().with_register(0xF, 0b11), 0x8F06);
+    assert_eq!(interpreter.registers()[0xF], 1);
+}
+
+#[test]
+fn logic_quirk_resets_vf() {
+    let builder = Chip8Interpreter::builder().with_register(0, 0b01).with_register(1, 0b10).with_register(0xF, 7);
+    for opcode in [0x8011, 0x8012, 0x8013] {
+        assert_eq!(run(builder.clone(), opcode).registers()[0xF], 7);
+        assert_eq!(run(builder.clone().with_quirks(Quirks::VIP), opcode).registers()[0xF], 0);
+    }
+}
+
+#[test]
+fn jump_quirk_uses_vx() {
+    let builder = Chip8Interpreter::builder().with_register(0, 4).with_register(3, 8);
+    assert_eq!(run(builder.clone(), 0xB320).program_counter(), 0x324);
+    assert_eq!(run(builder.with_quirks(Quirks::SCHIP), 0xB320).program_counter(), 0x328);
+}
+
+#[test]
+fn clip_quirk_cuts_sprites_at_the_edge() {
+    // An 8 pixel wide row drawn two pixels from the right edge
+    let builder = Chip8Interpreter::builder().with_register(0, 62).with_index(0x300).with_memory(0x300, &[0xFF]);
+    let wrapped = run(builder.clone(), 0xD011);
+    assert!(wrapped.vram()[62] && wrapped.vram()[63] && wrapped.vram()[0]);
+    let clipped = run(builder.with_quirks(Quirks::VIP), 0xD011);
+    assert!(clipped.vram()[62] && clipped.vram()[63] && !clipped.vram()[0]);
+}
+
+#[test]
+fn display_wait_quirk_ends_the_frame() {
+    // Draws and loops back, every draw waits for the next frame with the quirk
+    let rom = [0xD0, 0x01, 0x12, 0x00];
+    let mut modern = Chip8Interpreter::builder().with_rom(&rom).build();
+    modern.step_frame();
+    assert_eq!(modern.instruction_count(), modern.speed() as u64);
+    let mut vip = Chip8Interpreter::builder().with_rom(&rom).with_quirks(Quirks::VIP).build();
+    vip.step_frame();
+    assert_eq!(vip.instruction_count(), 1);
+    vip.step_frame();
+    assert_eq!(vip.instruction_count(), 3);
+}
+
+#[test]
+fn quirk_presets_by_name() {
+    for name in Quirks::PRESETS {
+        assert_eq!(Quirks::preset(name).and_then(|quirks| quirks.name()), Some(name));
+    }
+    assert_eq!(Quirks::preset("chip-8"), Some(Quirks::VIP));
+    assert_eq!(Quirks::default(), Quirks::MODERN);
+}
+
+#[test]
+fn mixed_quirks_by_name() {
+    let quirks = Quirks { clip_sprites: true, writes_font: true, ..Quirks::MODERN };
+    assert_eq!(quirks.to_string(), "load_store_increments_i,clip_sprites,writes_font");
+    assert_eq!(Quirks::parse(&quirks.to_string()), Some(quirks));
+    assert_eq!(Quirks::parse("vip"), Some(Quirks::VIP));
+    assert_eq!(Quirks::parse("none").map(|quirks| quirks.to_string()).as_deref(), Some("none"));
+    assert_eq!(Quirks::parse("clip_sprites,wrap"), None);
+}
+
+#[test]
+fn default_speed_runs_about_660_instructions_a_second() {
+    let mut interpreter = Chip8Interpreter::builder().with_rom(&[0x12, 0x00]).build();
+    assert_eq!(interpreter.speed(), DEFAULT_SPEED);
+    for _ in 0..FRAME_RATE {
+        interpreter.step_frame();
+    }
+    assert_eq!(interpreter.instruction_count(), 660);
+}
+
+#[test]
+fn vip_timing_runs_a_counting_loop_18_times_a_frame() {
+    // V0 += 1 and a jump back, 50 and 52 cycles with the fetch
+    let rom = [0x70, 0x01, 0x12, 0x00];
+    assert_eq!(vip_cycles(0x7001, &[0; 16], 0) + vip_cycles(0x1200, &[0; 16], 0), 102);
+    assert_eq!(VIP_FRAME_BUDGET, 18 * 102);
+    let mut interpreter = Chip8Interpreter::builder().with_rom(&rom).with_timing(Timing::Vip).build();
+    for _ in 0..10 {
+        interpreter.step_frame();
+    }
+    assert_eq!(interpreter.registers()[0], 180);
+}
+
+#[test]
+fn vip_skips_and_carries_cost_more() {
+    let mut registers = [0; 16];
+    assert_eq!(vip_cycles(0x3005, &registers, 0), 50);
+    registers[0] = 5;
+    assert_eq!(vip_cycles(0x3005, &registers, 0), 54);
+    assert_eq!(vip_cycles(0xF01E, &registers, 0x2FA), 52);
+    assert_eq!(vip_cycles(0xF01E, &registers, 0x2FB), 56);
+}
+
+#[test]
+fn changing_timing_starts_the_frame_over() {
+    let mut interpreter = Chip8Interpreter::builder().with_rom(&[0x70, 0x01, 0x12, 0x00]).with_timing(Timing::Vip).build();
+    for _ in 0..5 {
+        assert!(!interpreter.step_instruction());
+    }
+    interpreter.set_timing(Timing::Instructions);
+    let start = interpreter.instruction_count();
+    interpreter.step_frame();
+    assert_eq!(interpreter.instruction_count() - start, interpreter.speed() as u64);
+}
+
+#[test]
+fn vip_cost_of_an_instruction_that_wraps() {
+    // 00E0 split across the end of memory takes more than a frame, like it does anywhere else
+    let mut interpreter = Chip8Interpreter::builder()
+        .with_timing(Timing::Vip)
+        .with_memory(0xFFF, &[0x00])
+        .with_memory(0x000, &[0xE0])
+        .with_program_counter(0xFFF)
+        .build();
+    assert!(interpreter.step_instruction());
+    assert_eq!(interpreter.program_counter(), 0x001);
+}
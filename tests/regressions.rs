@@ -0,0 +1,85 @@
+//! Minimized inputs that used to panic or break an invariant, run through the same harness as the fuzzer
+
+use chip8::chip8::{Chip8Interpreter, Fault};
+use chip8::font::FONT_SET;
+use chip8::fuzz;
+use chip8::quirks::Quirks;
+
+/// Runs a ROM for a frame with the modern preset and no key events
+fn run(rom: &[u8]) {
+    let mut data = vec![0, 0, 0];
+    data.extend_from_slice(rom);
+    if let Err(violation) = fuzz::run(&data) {
+        panic!("{violation}");
+    }
+}
+
+/// Interpreter with a ROM loaded, after running `instructions` of it
+fn after(rom: &[u8], quirks: Quirks, instructions: usize) -> Chip8Interpreter {
+    let mut interpreter = Chip8Interpreter::new();
+    interpreter.set_quirks(quirks);
+    interpreter.set_seed(0);
+    interpreter.load_bytes(rom);
+    for _ in 0..instructions {
+        interpreter.execute_cycle();
+    }
+    interpreter
+}
+
+#[test]
+fn stack_overflow() {
+    run(&[0x22, 0x00]);
+    let interpreter = after(&[0x22, 0x00], Quirks::MODERN, 17);
+    assert_eq!(interpreter.fault(), Some(Fault::StackOverflow { pc: 0x200 }));
+    assert_eq!(interpreter.stack().len(), 16);
+}
+
+#[test]
+fn stack_underflow() {
+    run(&[0x00, 0xEE]);
+    let interpreter = after(&[0x00, 0xEE], Quirks::MODERN, 1);
+    assert_eq!(interpreter.fault(), Some(Fault::StackUnderflow { pc: 0x200 }));
+}
+
+#[test]
+fn sprite_past_end_of_memory() {
+    run(&[0xAF, 0xFF, 0xD0, 0x0F]);
+}
+
+#[test]
+fn bcd_past_end_of_memory() {
+    run(&[0xAF, 0xFF, 0xF0, 0x33]);
+}
+
+#[test]
+fn store_and_load_past_end_of_memory() {
+    run(&[0xAF, 0xFE, 0xFF, 0x55]);
+    run(&[0xAF, 0xFE, 0xFF, 0x65]);
+}
+
+#[test]
+fn index_overflow() {
+    // Keeps adding to I and storing, which moves I further each time
+    run(&[0x60, 0xFF, 0xF0, 0x1E, 0xFF, 0x55, 0x12, 0x02]);
+}
+
+#[test]
+fn pc_past_end_of_memory() {
+    run(&[0x1F, 0xFE]);
+    run(&[0x60, 0xFF, 0xBF, 0xFF]);
+}
+
+#[test]
+fn key_past_f() {
+    run(&[0x60, 0xFF, 0xE0, 0x9E, 0xE0, 0xA1]);
+}
+
+#[test]
+fn font_is_protected() {
+    let rom = [0xA0, 0x00, 0xFF, 0x55];
+    run(&rom);
+    let interpreter = after(&rom, Quirks::MODERN, 2);
+    assert_eq!(interpreter.memory()[..FONT_SET.len()], FONT_SET);
+    let interpreter = after(&rom, Quirks::XOCHIP, 2);
+    assert_eq!(interpreter.memory()[..16], [0; 16]);
+}
@@ -0,0 +1,38 @@
+//! Values read from the settings file
+#![cfg(feature = "std")]
+
+use chip8::chip8::{DEFAULT_SPEED, MAX_SPEED, MIN_SPEED};
+use chip8::settings::Settings;
+
+#[test]
+fn speed_is_rounded_and_clamped() {
+    let speed = |value: &str| {
+        let mut settings = Settings::default();
+        assert!(settings.set("speed", value));
+        settings.speed
+    };
+    assert_eq!(speed("660"), DEFAULT_SPEED);
+    assert_eq!(speed("689"), DEFAULT_SPEED);
+    assert_eq!(speed("690"), DEFAULT_SPEED + 1);
+    assert_eq!(speed("0"), MIN_SPEED);
+    assert_eq!(speed("4294967295"), MAX_SPEED);
+    assert!(!Settings::default().set("speed", "-1"));
+}
+
+#[test]
+fn mixed_quirks_are_saved() {
+    let mut settings = Settings::default();
+    assert!(settings.set("quirks", "shift_uses_vy,display_wait"));
+    assert!(settings.quirks.shift_uses_vy && settings.quirks.display_wait && !settings.quirks.clip_sprites);
+    let saved = settings.entries().into_iter().find(|(key, _)| *key == "quirks");
+    assert_eq!(saved, Some(("quirks", String::from("shift_uses_vy,display_wait"))));
+}
+
+#[test]
+fn keymap_leaves_hotkeys_alone() {
+    let mut settings = Settings::default();
+    assert!(settings.set("keymap", "0123456789abcdef"));
+    assert!(!settings.set("keymap", "0123456789abcdep"));
+    assert!(!settings.set("keymap", "0123456789abcde "));
+    assert_eq!(settings.keymap.to_string(), "0123456789abcdef");
+}
@@ -0,0 +1,153 @@
+//! Traces written while a short ROM runs, read back with `TraceReader`
+#![cfg(feature = "std")]
+
+use std::{cell::RefCell, io, rc::Rc};
+
+use chip8::chip8::Chip8Interpreter;
+use chip8::trace::{
+    first_divergence, OpcodePattern, TraceFormat, TraceReader, TraceRecord, Tracer, Trigger, BINARY_MAGIC, CSV_HEADER,
+};
+
+/// V0 = 1, then V0 += 1 and V1 += 1 forever from 202
+const ROM: [u8; 8] = [0x60, 0x01, 0x70, 0x01, 0x71, 0x01, 0x12, 0x02];
+/// Stores the digits of 123 at 300, then tries to store V0 and V1 over the font
+const STORE: [u8; 12] = [0xA3, 0x00, 0x60, 0x7B, 0xF0, 0x33, 0xA0, 0x00, 0xF1, 0x55, 0x12, 0x0A];
+
+/// Output that can still be read once the tracer owning it is gone
+#[derive(Clone, Default)]
+struct Shared(Rc<RefCell<Vec<u8>>>);
+
+impl io::Write for Shared {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Runs 10 instructions of `ROM` with a tracer set up by `setup`, returning what was written
+fn trace(format: TraceFormat, setup: impl FnOnce(Tracer) -> Tracer) -> Vec<u8> {
+    trace_rom(&ROM, format, setup)
+}
+
+fn trace_rom(rom: &[u8], format: TraceFormat, setup: impl FnOnce(Tracer) -> Tracer) -> Vec<u8> {
+    let output = Shared::default();
+    let mut interpreter = Chip8Interpreter::builder().with_rom(rom).build();
+    interpreter.set_tracer(Some(setup(Tracer::new(Box::new(output.clone()), format))));
+    for _ in 0..10 {
+        interpreter.execute_cycle();
+    }
+    interpreter.set_tracer(None);
+    output.0.take()
+}
+
+fn read(bytes: &[u8]) -> Vec<TraceRecord> {
+    TraceReader::new(bytes).unwrap().collect::<io::Result<_>>().unwrap()
+}
+
+fn cycles(records: &[TraceRecord]) -> Vec<u64> {
+    records.iter().map(|record| record.cycle).collect()
+}
+
+#[test]
+fn text_records_state_before_each_instruction() {
+    let records = read(&trace(TraceFormat::Text, |tracer| tracer));
+    assert_eq!(cycles(&records), (1..=10).collect::<Vec<_>>());
+    let pcs: Vec<u16> = records.iter().map(|record| record.pc).collect();
+    assert_eq!(pcs, [0x200, 0x202, 0x204, 0x206, 0x202, 0x204, 0x206, 0x202, 0x204, 0x206]);
+    // V0 was set by the first instruction and incremented by the second
+    assert_eq!(records[2].registers[0], 2);
+    assert_eq!(records[2].to_string(), "00000003 204 7101 V:02000000000000000000000000000000 I:000 SP:0 DT:00 ST:00");
+}
+
+#[test]
+fn binary_and_csv_round_trip() {
+    let text = read(&trace(TraceFormat::Text, |tracer| tracer));
+    let binary = trace(TraceFormat::Binary, |tracer| tracer);
+    assert!(binary.starts_with(BINARY_MAGIC));
+    assert_eq!(read(&binary), text);
+    let csv = trace(TraceFormat::Csv, |tracer| tracer);
+    assert!(csv.starts_with(CSV_HEADER.as_bytes()));
+    assert_eq!(read(&csv), text);
+    for record in &text {
+        assert_eq!(TraceRecord::from_bytes(&record.to_bytes()), *record);
+    }
+}
+
+#[test]
+fn range_filter() {
+    let records = read(&trace(TraceFormat::Text, |tracer| tracer.with_range(0x204..=0x204).with_range(0x200..=0x200)));
+    assert_eq!(cycles(&records), [1, 3, 6, 9]);
+}
+
+#[test]
+fn opcode_filter() {
+    let pattern = OpcodePattern::parse("7x01").unwrap();
+    let records = read(&trace(TraceFormat::Text, |tracer| tracer.with_opcode(pattern)));
+    assert_eq!(cycles(&records), [2, 3, 5, 6, 8, 9]);
+    assert_eq!(OpcodePattern::parse("7x0"), None);
+}
+
+#[test]
+fn start_and_stop_triggers() {
+    let start = Trigger::parse("cycle:4").unwrap();
+    let records = read(&trace(TraceFormat::Text, |tracer| tracer.with_start(start)));
+    assert_eq!(cycles(&records), [4, 5, 6, 7, 8, 9, 10]);
+    // Starts every time 204 is reached and stops at the jump, which is not traced
+    let (start, stop) = (Trigger::parse("204").unwrap(), Trigger::parse("0x206").unwrap());
+    let records = read(&trace(TraceFormat::Text, |tracer| tracer.with_start(start).with_stop(stop)));
+    assert_eq!(cycles(&records), [3, 6, 9]);
+}
+
+#[test]
+fn filters_apply_while_started() {
+    let start = Trigger::Cycle(5);
+    let pattern = OpcodePattern::parse("71kk").unwrap();
+    let records = read(&trace(TraceFormat::Csv, |tracer| tracer.with_start(start).with_opcode(pattern)));
+    assert_eq!(cycles(&records), [6, 9]);
+}
+
+#[test]
+fn records_what_each_instruction_stored() {
+    let records = read(&trace_rom(&STORE, TraceFormat::Text, |tracer| tracer));
+    assert_eq!(records[2].writes, [(0x300, 1), (0x301, 2), (0x302, 3)]);
+    assert!(records[2].to_string().ends_with(" ST:00 W:300=01,301=02,302=03"));
+    // The font is protected, so nothing was stored
+    assert_eq!(records[4].opcode, 0xF155);
+    assert_eq!(records[4].writes, []);
+    for format in [TraceFormat::Csv, TraceFormat::Binary] {
+        assert_eq!(read(&trace_rom(&STORE, format, |tracer| tracer)), records);
+    }
+}
+
+#[test]
+fn divergence_in_what_was_stored() {
+    let a = read(&trace_rom(&STORE, TraceFormat::Text, |tracer| tracer));
+    let mut b = a.clone();
+    b[2].writes[2].1 = 4;
+    let divergence = first_divergence(a.into_iter().map(Ok), b.into_iter().map(Ok), true, 1).unwrap().unwrap();
+    assert_eq!(divergence.cycle, 3);
+    assert_eq!(divergence.differences, ["writes 300=01,301=02,302=03 vs 300=01,301=02,302=04"]);
+    assert_eq!(cycles(&divergence.context), [2]);
+}
+
+#[test]
+fn filtered_traces_line_up_by_cycle() {
+    let all = || read(&trace(TraceFormat::Text, |tracer| tracer));
+    let compare = |a: Vec<TraceRecord>, b: Vec<TraceRecord>| {
+        first_divergence(a.into_iter().map(Ok), b.into_iter().map(Ok), true, 1).unwrap()
+    };
+    let ranged = read(&trace(TraceFormat::Text, |tracer| tracer.with_range(0x204..=0x204)));
+    assert!(compare(all(), ranged).is_none());
+    let started = read(&trace(TraceFormat::Text, |tracer| tracer.with_start(Trigger::Cycle(4))));
+    assert!(compare(started, all()).is_none());
+    // Still found on a cycle both traces have
+    let mut ranged = read(&trace(TraceFormat::Text, |tracer| tracer.with_range(0x204..=0x204)));
+    ranged[1].registers[1] = 9;
+    let divergence = compare(all(), ranged).unwrap();
+    assert_eq!(divergence.cycle, 6);
+    assert_eq!(divergence.differences, ["V1 01 vs 09"]);
+}
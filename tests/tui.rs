@@ -0,0 +1,28 @@
+//! Braille rendering of RGBA frames, which has to work for any palette
+#![cfg(feature = "tui")]
+
+use chip8::chip8::{Chip8Interpreter, Palette, HEIGHT, WIDTH};
+use chip8::tui::braille_lines;
+
+/// Frame with only the top left pixel lit, drawn with a palette
+fn frame(palette: Palette) -> Vec<u8> {
+    let mut interpreter = Chip8Interpreter::builder().with_index(0x300).with_memory(0x300, &[0x80]).build();
+    interpreter.set_palette(palette);
+    interpreter.write_u16(0x200, 0xD001);
+    interpreter.execute_cycle();
+    let mut pixels = vec![0; WIDTH * HEIGHT * 4];
+    interpreter.draw_pixels(&mut pixels);
+    pixels
+}
+
+#[test]
+fn lit_pixels_raise_dots() {
+    for palette in [Palette::MONO, Palette::AMBER, Palette::LCD] {
+        let lines = braille_lines(&frame(palette), &palette);
+        assert_eq!(lines.len(), HEIGHT / 4);
+        let mut first = lines[0].chars();
+        assert_eq!(first.next(), Some('\u{2801}'), "{palette}");
+        assert!(first.all(|c| c == '\u{2800}'), "{palette}");
+        assert!(lines[1..].iter().flat_map(|line| line.chars()).all(|c| c == '\u{2800}'), "{palette}");
+    }
+}
@@ -0,0 +1,54 @@
+//! Where the frame lands in the window and how it is scaled, without opening one
+#![cfg(feature = "window")]
+
+use chip8::chip8::{HEIGHT, WIDTH};
+use chip8::window::{blit_frame, frame_rect, ScalingMode, WindowOptions};
+
+fn options(scale: Option<u32>, scaling: ScalingMode) -> WindowOptions {
+    WindowOptions { scale, scaling, ..WindowOptions::default() }
+}
+
+#[test]
+fn pixel_perfect_uses_whole_scales() {
+    let rect = frame_rect(&options(None, ScalingMode::PixelPerfect), (200, 100));
+    assert_eq!(rect, (4, 2, 192, 96));
+}
+
+#[test]
+fn fit_keeps_aspect_ratio() {
+    let rect = frame_rect(&options(None, ScalingMode::Fit), (200, 50));
+    assert_eq!(rect, (50, 0, 100, 50));
+}
+
+#[test]
+fn stretch_fills_window() {
+    let rect = frame_rect(&options(None, ScalingMode::Stretch), (200, 50));
+    assert_eq!(rect, (0, 0, 200, 50));
+}
+
+#[test]
+fn fixed_scale_is_centred_and_clamped() {
+    let rect = frame_rect(&options(Some(2), ScalingMode::Fit), (640, 480));
+    assert_eq!(rect, (256, 208, 128, 64));
+    let rect = frame_rect(&options(Some(10), ScalingMode::Fit), (100, 100));
+    assert_eq!(rect, (0, 0, 100, 100));
+}
+
+#[test]
+fn blit_scales_and_fills_border() {
+    let mut frame = vec![0; WIDTH * HEIGHT * 4];
+    frame[..4].copy_from_slice(&[255, 255, 255, 255]);
+    let options = WindowOptions { border: [1, 2, 3], ..WindowOptions::default() };
+    // Scale 2 with a pixel of border on each side
+    let (width, height) = (WIDTH * 2 + 2, HEIGHT * 2 + 2);
+    let mut target = vec![0; width * height * 4];
+    blit_frame(&frame, &mut target, width, &options);
+    let pixel = |x: usize, y: usize| -> [u8; 4] { target[(y * width + x) * 4..][..4].try_into().unwrap() };
+    assert_eq!(pixel(0, 0), [1, 2, 3, 255]);
+    assert_eq!(pixel(width - 1, height - 1), [1, 2, 3, 255]);
+    for (x, y) in [(1, 1), (2, 1), (1, 2), (2, 2)] {
+        assert_eq!(pixel(x, y), [255, 255, 255, 255]);
+    }
+    assert_eq!(pixel(3, 1), [0, 0, 0, 0]);
+    assert_eq!(pixel(1, 3), [0, 0, 0, 0]);
+}